@@ -0,0 +1,223 @@
+use crate::error::Error;
+use crate::stream::text::TextBuffer;
+use chrono::{DateTime, Utc};
+use std::fmt::{self, Write};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+//1970-01-01 到 2001-01-01 的秒数和天数
+const UNIX_OFFSET: i64 = 978_307_200;
+const DAYS_TO_2001: i64 = 11_323;
+
+//plist 日期, 保存 2001-01-01T00:00:00Z 以来的秒数, 与二进制格式一致, 不丢失精度和范围
+//需要时再转换成 chrono 或 SystemTime
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Date {
+    seconds: f64,
+}
+impl Date {
+    pub fn from_seconds_since_2001(seconds: f64) -> Self {
+        Date { seconds }
+    }
+    pub fn seconds_since_2001(self) -> f64 {
+        self.seconds
+    }
+    //NaN 或超出 chrono 范围时返回 None, 小数部分四舍五入到纳秒
+    pub fn to_date_time(self) -> Option<DateTime<Utc>> {
+        let (whole, fraction) = self.split()?;
+        let mut seconds = whole.checked_add(UNIX_OFFSET)?;
+        let mut nanos = (fraction * 1e9).round() as u32;
+        if nanos >= 1_000_000_000 {
+            seconds = seconds.checked_add(1)?;
+            nanos -= 1_000_000_000;
+        }
+        DateTime::from_timestamp(seconds, nanos)
+    }
+    pub fn to_system_time(self) -> Option<SystemTime> {
+        self.to_date_time().map(SystemTime::from)
+    }
+    //拆分成整秒和不足一秒的小数部分, 两者相加就是原来的值
+    //只有 (-1, 0) 之间绝对值极小的负数, 小数部分会被舍入成 1
+    fn split(self) -> Option<(i64, f64)> {
+        let whole = self.whole()?;
+        let fraction = self.seconds - whole as f64;
+        if fraction >= 1.0 {
+            return Some((whole + 1, 0.0));
+        }
+        Some((whole, fraction))
+    }
+    //向下取整的秒数, NaN 或超出 i64 范围时返回 None
+    fn whole(self) -> Option<i64> {
+        let whole = self.seconds.floor();
+        if !(i64::MIN as f64..i64::MAX as f64).contains(&whole) {
+            return None;
+        }
+        Some(whole as i64)
+    }
+    //XML 中的写法, NaN 或超出范围时返回 None; apple_style 只保留整秒
+    pub(crate) fn xml_text(self, fraction: bool) -> Option<XmlDate> {
+        Some(XmlDate {
+            seconds: self.seconds,
+            whole: self.whole()?,
+            fraction,
+        })
+    }
+}
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.xml_text(true) {
+            Some(text) => text.fmt(f),
+            None => write!(f, "{} seconds since 2001", self.seconds),
+        }
+    }
+}
+//直接写入输出, 不生成中间字符串: 年份至少 4 位, 有小数时写出能精确还原的最短小数
+pub(crate) struct XmlDate {
+    seconds: f64,
+    whole: i64,
+    fraction: bool,
+}
+impl fmt::Display for XmlDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let days = self.whole.div_euclid(86_400) + DAYS_TO_2001;
+        let time = self.whole.rem_euclid(86_400);
+        let (year, month, day) = civil_from_days(days);
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            time / 3600,
+            time / 60 % 60,
+            time % 60
+        )?;
+        //小数部分直接取自最短十进制写法, 负数取补: -0.25 写成 23:59:59.75
+        //不经过 f64 减法, 绝对值极小的负数也不会被舍入成整秒
+        let mut shortest = TextBuffer::default();
+        write!(shortest, "{}", self.seconds.abs())?;
+        if let Some((_, digits)) = shortest.as_str().split_once('.')
+            && self.fraction
+        {
+            f.write_char('.')?;
+            if self.seconds < 0.0 {
+                complement(digits).try_for_each(|c| f.write_char(c))?;
+            } else {
+                f.write_str(digits)?;
+            }
+        }
+        f.write_char('Z')
+    }
+}
+//只接受 2024-08-17T02:24:50Z 和带小数秒的 2024-08-17T02:24:50.25Z, 年份可以为负数或超过 4 位
+impl FromStr for Date {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_iso(s).ok_or_else(|| Error::Error(format!("Invalid date: {:?}", s)))
+    }
+}
+impl From<DateTime<Utc>> for Date {
+    fn from(value: DateTime<Utc>) -> Self {
+        let seconds = (value.timestamp() - UNIX_OFFSET) as f64;
+        Date::from_seconds_since_2001(seconds + value.timestamp_subsec_nanos() as f64 / 1e9)
+    }
+}
+impl From<SystemTime> for Date {
+    fn from(value: SystemTime) -> Self {
+        DateTime::<Utc>::from(value).into()
+    }
+}
+
+fn parse_iso(s: &str) -> Option<Date> {
+    let (date, time) = s.strip_suffix('Z')?.split_once('T')?;
+    let mut parts = date.rsplitn(3, '-');
+    let day = digits(parts.next()?, 2)?;
+    let month = digits(parts.next()?, 2)?;
+    let year_text = parts.next()?;
+    let unsigned = year_text.strip_prefix('-').unwrap_or(year_text);
+    if unsigned.is_empty() || !unsigned.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let year = year_text.parse::<i64>().ok()? as i128;
+    let (time, fraction) = match time.split_once('.') {
+        Some((time, fraction)) if !fraction.is_empty() => {
+            if !fraction.bytes().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            (time, fraction)
+        }
+        Some(_) => return None,
+        None => (time, ""),
+    };
+    let mut fields = time.split(':');
+    let hour = digits(fields.next()?, 2)?;
+    let minute = digits(fields.next()?, 2)?;
+    let second = digits(fields.next()?, 2)?;
+    if fields.next().is_some() || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    let days = days_from_civil(year, month, day) - DAYS_TO_2001 as i128;
+    let whole = days * 86_400 + (hour * 3600 + minute * 60 + second) as i128;
+    //先拼成完整的十进制数再转换, 只舍入一次; 负数的小数部分取补, 与写出时对应
+    let fraction = fraction.trim_end_matches('0');
+    let seconds = if fraction.is_empty() {
+        whole as f64
+    } else if whole >= 0 {
+        format!("{}.{}", whole, fraction).parse().ok()?
+    } else {
+        let fraction: String = complement(fraction).collect();
+        format!("-{}.{}", -whole - 1, fraction).parse().ok()?
+    };
+    Some(Date::from_seconds_since_2001(seconds))
+}
+//1 减去小数 0.digits 的十进制小数位, digits 的最后一位不能是 0
+fn complement(digits: &str) -> impl Iterator<Item = char> + '_ {
+    let last = digits.len() - 1;
+    digits.bytes().enumerate().map(move |(i, c)| {
+        let nines = if i == last { b'9' + 1 } else { b'9' };
+        (nines - c + b'0') as char
+    })
+}
+fn digits(text: &str, len: usize) -> Option<i64> {
+    if text.len() != len || !text.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    text.parse().ok()
+}
+fn days_in_month(year: i128, month: i64) -> i64 {
+    let next = match month {
+        12 => days_from_civil(year + 1, 1, 1),
+        _ => days_from_civil(year, month + 1, 1),
+    };
+    (next - days_from_civil(year, month, 1)) as i64
+}
+//公历 (含公元 0 年) 日期与 1970-01-01 以来天数的互相转换, 算法来自 Howard Hinnant 的 chrono-Compatible Low-Level Date Algorithms
+fn days_from_civil(year: i128, month: i64, day: i64) -> i128 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = ((month + 9) % 12) as i128;
+    let day_of_year = (153 * month_index + 2) / 5 + day as i128 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+fn civil_from_days(days: i64) -> (i128, i64, i64) {
+    let days = days as i128 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as i64;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as i64;
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
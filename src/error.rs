@@ -1,18 +1,82 @@
 use thiserror::Error;
 
+//解析错误的具体类型
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ErrorKind {
+    #[error("unexpected end of input")]
+    UnexpectedEof,
+    #[error("invalid binary plist header")]
+    InvalidHeader,
+    #[error("invalid binary plist trailer: {0}")]
+    InvalidTrailer(&'static str),
+    #[error("invalid object marker 0x{0:02X}")]
+    InvalidMarker(u8),
+    #[error("invalid UTF-8 text")]
+    InvalidUtf8,
+    #[error("invalid UTF-16 text")]
+    InvalidUtf16,
+    #[error("unsupported encoding {0:?}")]
+    UnsupportedEncoding(String),
+    #[error("invalid integer {0:?}")]
+    BadInteger(String),
+    #[error("invalid real {0:?}")]
+    BadReal(String),
+    #[error("invalid date {0:?}")]
+    BadDate(String),
+    #[error("invalid base64 data")]
+    BadData,
+    #[error("invalid entity {0:?}")]
+    BadEntity(String),
+    #[error("duplicate key {0:?}")]
+    DuplicateKey(String),
+    #[error("dictionary key is not a string")]
+    InvalidKey,
+    #[error("unknown tag <{0}>")]
+    UnknownTag(String),
+    #[error("invalid XML syntax")]
+    InvalidSyntax,
+    #[error("object reference {0} out of range")]
+    ObjectRefOutOfRange(u64),
+    #[error("object offset {0} out of range")]
+    OffsetOutOfRange(u64),
+    #[error("object {0} references itself")]
+    RecursiveObject(u64),
+    #[error("maximum nesting depth {0} exceeded")]
+    DepthLimitExceeded(usize),
+    #[error("maximum object count {0} exceeded")]
+    ObjectLimitExceeded(usize),
+}
+
 #[derive(Debug, Error)]
 pub enum Error {
+    //二进制 plist 的错误位置是字节偏移
+    #[error("{kind} at byte offset {offset}")]
+    Binary { kind: ErrorKind, offset: usize },
+    //XML plist 的错误位置是行号和列号, 从 1 开始
+    #[error("{kind} at line {line}, column {column}")]
+    Xml {
+        kind: ErrorKind,
+        line: usize,
+        column: usize,
+    },
     #[error("{0}")]
     Error(String),
     #[error(transparent)]
     IoError(#[from] std::io::Error),
-    #[error(transparent)]
-    StdError(#[from] std::num::ParseIntError),
-    #[error(transparent)]
-    ParseError(nom::Err<nom::error::Error<Box<str>>>),
 }
-impl From<nom::Err<nom::error::Error<&str>>> for Error {
-    fn from(err: nom::Err<nom::error::Error<&str>>) -> Self {
-        Self::ParseError(err.map_input(|input| input.into()))
+impl Error {
+    pub fn kind(&self) -> Option<&ErrorKind> {
+        match self {
+            Error::Binary { kind, .. } | Error::Xml { kind, .. } => Some(kind),
+            _ => None,
+        }
+    }
+    //根据已解析的文本计算行号和列号
+    pub(crate) fn xml(kind: ErrorKind, text: &str, offset: usize) -> Self {
+        let prefix = &text[..offset];
+        let line = prefix.matches('\n').count() + 1;
+        let line_start = prefix.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let column = prefix[line_start..].chars().count() + 1;
+        Error::Xml { kind, line, column }
     }
 }
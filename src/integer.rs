@@ -0,0 +1,62 @@
+use crate::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+//plist 整数, 取值范围 i64::MIN..=u64::MAX, 同时保留有符号和无符号的信息
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Integer {
+    value: i128,
+}
+impl Integer {
+    pub const MIN: Integer = Integer {
+        value: i64::MIN as i128,
+    };
+    pub const MAX: Integer = Integer {
+        value: u64::MAX as i128,
+    };
+    pub fn as_signed(self) -> Option<i64> {
+        i64::try_from(self.value).ok()
+    }
+    pub fn as_unsigned(self) -> Option<u64> {
+        u64::try_from(self.value).ok()
+    }
+    pub fn is_negative(self) -> bool {
+        self.value < 0
+    }
+    pub(crate) fn from_i128(value: i128) -> Option<Self> {
+        if (Self::MIN.value..=Self::MAX.value).contains(&value) {
+            Some(Integer { value })
+        } else {
+            None
+        }
+    }
+    pub(crate) fn to_i128(self) -> i128 {
+        self.value
+    }
+}
+impl fmt::Display for Integer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+impl FromStr for Integer {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<i128>()
+            .ok()
+            .and_then(Self::from_i128)
+            .ok_or_else(|| Error::Error(format!("Invalid integer: {:?}", s)))
+    }
+}
+macro_rules! impl_from {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Integer {
+                fn from(value: $t) -> Self {
+                    Integer { value: value as i128 }
+                }
+            }
+        )*
+    };
+}
+impl_from!(i8, i16, i32, i64, u8, u16, u32, u64);
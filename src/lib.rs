@@ -1,3 +1,7 @@
 pub mod plist;
+pub mod date;
 pub mod error;
+pub mod integer;
+pub mod options;
+pub mod real;
 pub(crate) mod stream;
\ No newline at end of file
@@ -0,0 +1,121 @@
+//二进制 plist 写入时的对象去重策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Deduplication {
+    //不去重, 每个值都写成独立的对象
+    None,
+    //只对字符串、数字、日期、数据等标量去重
+    #[default]
+    Scalars,
+    //标量和内容相同的数组、字典都去重
+    All,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BinaryWriteOptions {
+    pub deduplication: Deduplication,
+    //按 Apple 生成的二进制文件的对象布局输出: 字符串和数值去重、容器和 UID 不去重、key 保持原来的顺序, 此时忽略 deduplication
+    //不是 plutil 兼容模式: 只保证重新写出 Apple 生成的文件时逐字节相同, 其它输入与 plutil -convert binary1 的输出可能不同
+    pub apple_style: bool,
+}
+impl BinaryWriteOptions {
+    pub fn apple_style() -> Self {
+        BinaryWriteOptions {
+            apple_style: true,
+            ..Default::default()
+        }
+    }
+}
+
+//XML plist 的输出格式
+#[derive(Debug, Clone)]
+pub struct XmlWriteOptions {
+    //每一级缩进使用的字符串
+    pub indent: String,
+    //输出 <?xml version="1.0" encoding="UTF-8"?>
+    pub declaration: bool,
+    //输出 Apple 的 <!DOCTYPE plist ...>
+    pub doctype: bool,
+    //紧凑模式: 所有内容写在一行, 不换行也不缩进
+    pub compact: bool,
+    //</plist> 之后追加换行
+    pub trailing_newline: bool,
+    //模仿 Apple 工具的写法: key 排序、data 按行折叠、实数使用 %.17g、空数组写成 <array/>、日期只写整秒
+    //不是 plutil 兼容模式, 输出与 plutil -convert xml1 不保证逐字节相同
+    pub apple_style: bool,
+}
+impl XmlWriteOptions {
+    pub fn apple_style() -> Self {
+        XmlWriteOptions {
+            trailing_newline: true,
+            apple_style: true,
+            ..Default::default()
+        }
+    }
+}
+impl Default for XmlWriteOptions {
+    fn default() -> Self {
+        XmlWriteOptions {
+            indent: "\t".to_string(),
+            declaration: true,
+            doctype: true,
+            compact: false,
+            trailing_newline: false,
+            apple_style: false,
+        }
+    }
+}
+
+//字典中出现重复 key 时的处理策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateKeys {
+    //返回错误
+    Error,
+    //保留第一次出现的值
+    KeepFirst,
+    //保留最后一次出现的值
+    #[default]
+    KeepLast,
+}
+
+//Plist::parse_with 的解析选项, 同时作用于 XML 和二进制格式
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    //严格模式: 二进制字典的 key 必须是字符串, XML 的 <plist> 前后不能有其他内容
+    //非严格模式下 XML 文档前后的任意字节都会被跳过, 例如描述文件的签名数据
+    pub strict: bool,
+    //数组和字典的最大嵌套深度
+    pub max_depth: usize,
+    //最多解析的对象数, 二进制中被多次引用的对象重复计数
+    //默认 None 表示取输入的字节数和 DEFAULT_MAX_OBJECTS 中较大的一个: 没有共享容器的 plist 不会超出,
+    //Deduplication::All 写出的共享容器在这个范围内可以正常展开, 层层重复引用同一容器的恶意文件最多展开这么多个对象
+    pub max_objects: Option<usize>,
+    pub duplicate_keys: DuplicateKeys,
+    //XML 日期允许时区偏移、省略 Z 和只有日期的写法
+    pub lenient_dates: bool,
+    //XML 中的 <dict><key>CF$UID</key><integer>N</integer></dict> 还原为 Plist::Uid
+    pub recover_uids: bool,
+    //保留 XML 中 <real> 的原始写法, 写出 XML 时原样输出
+    pub keep_real_text: bool,
+}
+//未设置 max_objects 时至少允许展开的对象数
+pub const DEFAULT_MAX_OBJECTS: usize = 1 << 20;
+
+impl ParseOptions {
+    pub(crate) fn object_limit(&self, input: &[u8]) -> usize {
+        self.max_objects
+            .unwrap_or(input.len().max(DEFAULT_MAX_OBJECTS))
+    }
+}
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            strict: false,
+            max_depth: 512,
+            max_objects: None,
+            duplicate_keys: DuplicateKeys::default(),
+            lenient_dates: true,
+            recover_uids: false,
+            keep_real_text: false,
+        }
+    }
+}
@@ -1,22 +1,28 @@
+use crate::date::Date;
 use crate::error::Error;
+use crate::integer::Integer;
+use crate::options::{BinaryWriteOptions, ParseOptions, XmlWriteOptions};
+use crate::real::Real;
 use crate::stream::binary_reader::BinaryReader;
 use crate::stream::binary_writer::BinaryWriter;
 use crate::stream::xml_reader::XmlReader;
 use crate::stream::xml_writer::XmlWriter;
-use chrono::{DateTime, Utc};
 use indexmap::IndexMap;
-use std::io::Cursor;
+use std::io::{BufWriter, Cursor, Write};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Plist {
     Array(Vec<Plist>),
+    //二进制格式中的集合 (0xC, NSSet); XML 没有集合类型, 写成 <array>, 读回来是 Array
+    Set(Vec<Plist>),
     Dictionary(IndexMap<String, Plist>),
     Boolean(bool),
-    Integer(i64),
-    Float(f64),
+    Integer(Integer),
+    Float(Real),
     String(String),
-    Date(DateTime<Utc>),
+    Date(Date),
     Data(Vec<u8>),
+    Uid(u64),
 }
 impl Plist {
     pub fn get_or_init_dict(&mut self, fkey: &str) -> Result<&mut Self, Error> {
@@ -31,13 +37,23 @@ impl Plist {
         })
     }
     pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        Self::parse_with(data, &ParseOptions::default())
+    }
+    pub fn parse_with(data: &[u8], options: &ParseOptions) -> Result<Self, Error> {
         if data.starts_with(b"bplist00") {
-            let (_, value) = BinaryReader::parse(data).map_err(|e| Error::Error(e.to_string()))?;
-            Ok(value)
+            BinaryReader::parse_with(data, options)
         } else {
-            XmlReader::parse(data)
+            XmlReader::parse_with(data, options)
         }
     }
+    //NSKeyedArchiver 文件: XML 中的 <dict><key>CF$UID</key><integer>N</integer></dict> 还原为 Plist::Uid
+    pub fn parse_keyed_archive(data: &[u8]) -> Result<Self, Error> {
+        let options = ParseOptions {
+            recover_uids: true,
+            ..Default::default()
+        };
+        Self::parse_with(data, &options)
+    }
     pub fn insert(&mut self, key: &str, value: Plist) -> Result<(), Error> {
         match self {
             Plist::Dictionary(dict) => {
@@ -69,17 +85,37 @@ impl From<bool> for Plist {
 }
 impl From<i64> for Plist {
     fn from(value: i64) -> Self {
+        Plist::Integer(value.into())
+    }
+}
+impl From<u64> for Plist {
+    fn from(value: u64) -> Self {
+        Plist::Integer(value.into())
+    }
+}
+impl From<Integer> for Plist {
+    fn from(value: Integer) -> Self {
         Plist::Integer(value)
     }
 }
 impl From<f64> for Plist {
     fn from(value: f64) -> Self {
-        Plist::Float(value)
+        Plist::Float(value.into())
     }
 }
 impl From<f32> for Plist {
     fn from(value: f32) -> Self {
-        Plist::Float(value as f64)
+        Plist::Float(value.into())
+    }
+}
+impl From<Real> for Plist {
+    fn from(value: Real) -> Self {
+        Plist::Float(value)
+    }
+}
+impl From<Date> for Plist {
+    fn from(value: Date) -> Self {
+        Plist::Date(value)
     }
 }
 impl From<&str> for Plist {
@@ -105,7 +141,10 @@ impl From<String> for Plist {
 #[allow(dead_code)]
 impl Plist {
     pub fn to_binary(&self) -> Result<Vec<u8>, Error> {
-        let plist_write = BinaryWriter::new();
+        self.to_binary_with(&BinaryWriteOptions::default())
+    }
+    pub fn to_binary_with(&self, options: &BinaryWriteOptions) -> Result<Vec<u8>, Error> {
+        let plist_write = BinaryWriter::new(options);
         let mut output = Cursor::new(vec![]);
         plist_write.write(self, &mut output)?;
         Ok(output.into_inner())
@@ -114,20 +153,31 @@ impl Plist {
         if data.starts_with(b"bplist00") {
             self.to_binary()
         } else {
-            Ok(self.to_xml().as_bytes().to_vec())
+            let mut output = vec![];
+            self.write_xml(&mut output)?;
+            Ok(output)
         }
     }
-    pub fn to_xml(&self) -> String {
-        let mut xml = String::from(
-            r#"<?xml version="1.0" encoding="UTF-8"?>
-<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
-<plist version="1.0">
-"#,
-        );
-
-        xml.push_str(&self.convert_xml(0));
-        xml.push_str("</plist>");
-        xml
+    //流式写入 XML, 写入文件时内部使用 BufWriter 缓冲
+    pub fn write_xml<W: Write>(&self, output: W) -> Result<(), Error> {
+        self.write_xml_with(output, &XmlWriteOptions::default())
+    }
+    pub fn write_xml_with<W: Write>(
+        &self,
+        output: W,
+        options: &XmlWriteOptions,
+    ) -> Result<(), Error> {
+        XmlWriter::new(BufWriter::new(output), options).write(self)
+    }
+    pub fn to_xml(&self) -> Result<String, Error> {
+        self.to_xml_with(&XmlWriteOptions::default())
+    }
+    //NaN 或超出范围的日期无法写成 XML, 此时返回错误
+    pub fn to_xml_with(&self, options: &XmlWriteOptions) -> Result<String, Error> {
+        let mut output = vec![];
+        XmlWriter::new(&mut output, options).write(self)?;
+        //输出总是合法的 UTF-8
+        Ok(String::from_utf8(output).unwrap_or_default())
     }
     pub fn sort_key(&mut self) {
         if let Plist::Dictionary(dict) = self {
@@ -138,19 +188,326 @@ impl Plist {
 
 #[cfg(test)]
 mod bplist_test {
-    use crate::plist::BPlist;
+    use crate::error::{Error, ErrorKind};
+    use crate::options::{
+        BinaryWriteOptions, DEFAULT_MAX_OBJECTS, Deduplication, DuplicateKeys, ParseOptions,
+    };
+    use crate::plist::Plist;
+    use crate::stream::binary_reader::BinaryReader;
     use std::fs;
 
     #[test]
     fn test_parse_binary() {
         let data = fs::read("./data/InfoPlist.strings").unwrap();
-        let (input, plist) = BPlist::parse(&data).unwrap();
+        let plist = BinaryReader::parse_with(&data, &ParseOptions::default()).unwrap();
         println!("{:?}", plist)
     }
+
+    //用于损坏测试的合法文件
+    fn corpus() -> Vec<Vec<u8>> {
+        let mut dict = Plist::Dictionary(Default::default());
+        dict.insert("name", "ipadump".into()).unwrap();
+        dict.insert("unicode", "中文字符串 with more than fifteen".into())
+            .unwrap();
+        dict.insert("data", vec![0_u8; 40].into()).unwrap();
+        dict.insert("negative", Plist::from(-1_i64)).unwrap();
+        dict.insert("large", Plist::from(u64::MAX)).unwrap();
+        dict.insert("real", Plist::from(1.5_f64)).unwrap();
+        dict.insert("uid", Plist::Uid(300)).unwrap();
+        dict.insert("flag", true.into()).unwrap();
+        let list = (0..20_i64).map(Plist::from).collect();
+        dict.insert("list", Plist::Array(list)).unwrap();
+        vec![
+            fs::read("./data/Info.plist").unwrap(),
+            fs::read("./data/InfoPlist.strings").unwrap(),
+            dict.to_binary().unwrap(),
+        ]
+    }
+
+    #[test]
+    fn test_truncated_binary() {
+        for data in corpus() {
+            assert!(Plist::parse(&data).is_ok());
+            for len in 0..data.len() {
+                assert!(Plist::parse(&data[..len]).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_corrupted_trailer() {
+        for data in corpus() {
+            let trailer_start = data.len() - 32;
+            //尾部的每个字节都替换成几个典型值, 不能 panic
+            for index in trailer_start..data.len() {
+                for byte in [0x00, 0x01, 0x07, 0x08, 0x7F, 0x80, 0xFF] {
+                    let mut corrupted = data.clone();
+                    corrupted[index] = byte;
+                    let _ = Plist::parse(&corrupted);
+                }
+                let mut corrupted = data.clone();
+                corrupted[index] = corrupted[index].wrapping_add(1);
+                let _ = Plist::parse(&corrupted);
+            }
+            let mut corrupted = data.clone();
+            corrupted[trailer_start + 8..trailer_start + 16]
+                .copy_from_slice(&u64::MAX.to_be_bytes());
+            assert!(Plist::parse(&corrupted).is_err());
+            let mut corrupted = data.clone();
+            corrupted[trailer_start + 24..].copy_from_slice(&u64::MAX.to_be_bytes());
+            assert!(Plist::parse(&corrupted).is_err());
+        }
+    }
+
+    #[test]
+    fn test_corrupted_bytes() {
+        //每个字节都尝试翻转每一位以及几个典型值, 包括会形成循环引用的修改
+        for data in corpus() {
+            for index in 8..data.len() {
+                let flips = (0..8).map(|bit| data[index] ^ (1 << bit));
+                let specials = [0x00, 0x0F, 0x13, 0x1F, 0x6F, 0x8F, 0xAF, 0xCF, 0xDF, 0xFF];
+                for byte in flips.chain(specials) {
+                    let mut corrupted = data.clone();
+                    corrupted[index] = byte;
+                    let _ = Plist::parse(&corrupted);
+                }
+            }
+        }
+    }
+
+    //用 1 字节偏移和引用手工拼装 bplist
+    fn bplist(objects: &[&[u8]]) -> Vec<u8> {
+        let mut data = b"bplist00".to_vec();
+        let mut offsets = vec![];
+        for object in objects {
+            offsets.push(data.len() as u8);
+            data.extend(*object);
+        }
+        let table_start = data.len() as u64;
+        data.extend(offsets);
+        data.extend([0, 0, 0, 0, 0, 0, 1, 1]);
+        data.extend((objects.len() as u64).to_be_bytes());
+        data.extend(0_u64.to_be_bytes());
+        data.extend(table_start.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn test_recursive_objects() {
+        let data = bplist(&[&[0xA1, 0x00]]);
+        assert!(matches!(
+            Plist::parse(&data),
+            Err(Error::Binary {
+                kind: ErrorKind::RecursiveObject(0),
+                offset: 8
+            })
+        ));
+        let data = bplist(&[&[0xA1, 0x01], &[0xD1, 0x02, 0x00], b"\x51k"]);
+        assert!(matches!(
+            Plist::parse(&data),
+            Err(Error::Binary {
+                kind: ErrorKind::RecursiveObject(0),
+                offset: 8
+            })
+        ));
+        //共享对象不是循环引用
+        let data = bplist(&[&[0xA2, 0x01, 0x01], &[0xA1, 0x02], &[0x09]]);
+        let shared = Plist::Array(vec![Plist::Boolean(true)]);
+        assert_eq!(
+            Plist::parse(&data).unwrap(),
+            Plist::Array(vec![shared.clone(), shared])
+        );
+    }
+
+    #[test]
+    fn test_error_offsets() {
+        let error = |data: &[u8]| match BinaryReader::parse_with(data, &ParseOptions::default()) {
+            Err(Error::Binary { kind, offset }) => (kind, offset),
+            other => panic!("unexpected result {:?}", other),
+        };
+        assert_eq!(
+            error(&bplist(&[&[0xA1, 0x01], &[0x70]])),
+            (ErrorKind::InvalidMarker(0x70), 10)
+        );
+        assert_eq!(
+            error(&bplist(&[&[0xA2, 0x01, 0x05], &[0x09]])),
+            (ErrorKind::ObjectRefOutOfRange(5), 10)
+        );
+        assert_eq!(
+            error(&bplist(&[&[0x53, b'a']])),
+            (ErrorKind::UnexpectedEof, 9)
+        );
+        assert_eq!(error(b"bplist01"), (ErrorKind::InvalidHeader, 0));
+        let mut data = bplist(&[&[0x09]]);
+        let trailer_start = data.len() - 32;
+        data[trailer_start + 6] = 0;
+        assert_eq!(
+            error(&data),
+            (
+                ErrorKind::InvalidTrailer("invalid offset size"),
+                trailer_start + 6
+            )
+        );
+        let mut data = bplist(&[&[0x09]]);
+        data[9] = 0x40;
+        assert_eq!(error(&data), (ErrorKind::OffsetOutOfRange(0x40), 9));
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let data = bplist(&[&[0xD2, 1, 1, 2, 3], b"\x51k", &[0x09], &[0x08]]);
+        let parse = |duplicate_keys| {
+            let options = ParseOptions {
+                duplicate_keys,
+                ..Default::default()
+            };
+            Plist::parse_with(&data, &options)
+        };
+        let mut expected = Plist::Dictionary(Default::default());
+        expected.insert("k", Plist::Boolean(false)).unwrap();
+        assert_eq!(parse(DuplicateKeys::KeepLast).unwrap(), expected);
+        expected.insert("k", Plist::Boolean(true)).unwrap();
+        assert_eq!(parse(DuplicateKeys::KeepFirst).unwrap(), expected);
+        assert!(matches!(
+            parse(DuplicateKeys::Error),
+            Err(Error::Binary {
+                kind: ErrorKind::DuplicateKey(key),
+                offset: 13
+            }) if key == "k"
+        ));
+    }
+
+    #[test]
+    fn test_non_string_keys() {
+        //第一个 key 是整数, 第二个 key 的值不能错位
+        let data = bplist(&[&[0xD2, 1, 2, 3, 4], &[0x10, 5], b"\x51k", &[0x09], &[0x08]]);
+        let mut expected = Plist::Dictionary(Default::default());
+        expected.insert("k", Plist::Boolean(false)).unwrap();
+        assert_eq!(Plist::parse(&data).unwrap(), expected);
+        let options = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Plist::parse_with(&data, &options),
+            Err(Error::Binary {
+                kind: ErrorKind::InvalidKey,
+                offset: 13
+            })
+        ));
+    }
+
+    #[test]
+    fn test_set() {
+        let data = bplist(&[&[0xC2, 1, 2], b"\x51a", &[0x10, 7]]);
+        let value = Plist::Set(vec!["a".into(), Plist::from(7_i64)]);
+        assert_eq!(Plist::parse(&data).unwrap(), value);
+        assert_eq!(value.to_binary().unwrap(), data);
+        assert_eq!(
+            value
+                .to_binary_with(&BinaryWriteOptions::apple_style())
+                .unwrap(),
+            data
+        );
+        //集合中的数组和集合中的集合
+        let nested = Plist::Array(vec![
+            Plist::Set(vec![Plist::Array(vec![1_i64.into()])]),
+            Plist::Set(vec![Plist::Set(vec![])]),
+        ]);
+        assert_eq!(Plist::parse(&nested.to_binary().unwrap()).unwrap(), nested);
+        //XML 没有集合, 写成数组
+        let xml = value.to_xml().unwrap();
+        assert!(xml.contains("<array>"));
+        assert_eq!(
+            Plist::parse(xml.as_bytes()).unwrap(),
+            Plist::Array(vec!["a".into(), Plist::from(7_i64)])
+        );
+        let data = bplist(&[&[0xC1, 0x00]]);
+        assert!(matches!(
+            Plist::parse(&data),
+            Err(Error::Binary {
+                kind: ErrorKind::RecursiveObject(0),
+                offset: 8
+            })
+        ));
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let mut value = Plist::Array(vec![]);
+        for _ in 0..100 {
+            value = Plist::Array(vec![value]);
+        }
+        let data = value.to_binary().unwrap();
+        assert_eq!(Plist::parse(&data).unwrap(), value);
+        let options = ParseOptions {
+            max_depth: 50,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Plist::parse_with(&data, &options),
+            Err(Error::Binary {
+                kind: ErrorKind::DepthLimitExceeded(50),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_object_limit() {
+        //每层数组两次引用下一层, 展开后有 2^40 个对象
+        let objects: Vec<Vec<u8>> = (1..=40_u8)
+            .map(|next| vec![0xA2, next, next])
+            .chain([vec![0x09]])
+            .collect();
+        let objects: Vec<&[u8]> = objects.iter().map(|o| o.as_slice()).collect();
+        let data = bplist(&objects);
+        let options = ParseOptions {
+            max_objects: Some(10000),
+            ..Default::default()
+        };
+        assert!(matches!(
+            Plist::parse_with(&data, &options),
+            Err(Error::Binary {
+                kind: ErrorKind::ObjectLimitExceeded(10000),
+                ..
+            })
+        ));
+        //默认选项同样能及时拦截
+        assert!(matches!(
+            Plist::parse(&data),
+            Err(Error::Binary {
+                kind: ErrorKind::ObjectLimitExceeded(DEFAULT_MAX_OBJECTS),
+                ..
+            })
+        ));
+        //大量引用同一个标量的正常文件不受影响
+        let value = Plist::Array(vec![Plist::Boolean(true); 100_000]);
+        assert_eq!(Plist::parse(&value.to_binary().unwrap()).unwrap(), value);
+        //Deduplication::All 写出的共享容器在默认选项下可以读回: 同一个 50 个整数的数组被引用 200 次
+        let shared = Plist::Array((0..50_i64).map(Plist::from).collect());
+        let value = Plist::Array(vec![shared; 200]);
+        let options = BinaryWriteOptions {
+            deduplication: Deduplication::All,
+            ..Default::default()
+        };
+        let data = value.to_binary_with(&options).unwrap();
+        assert!(data.len() < 1000);
+        assert_eq!(Plist::parse(&data).unwrap(), value);
+    }
 }
+
 #[cfg(test)]
 mod plist_test {
+    use crate::date::Date;
+    use crate::error::{Error, ErrorKind};
+    use crate::integer::Integer;
+    use crate::options::{
+        BinaryWriteOptions, Deduplication, DuplicateKeys, ParseOptions, XmlWriteOptions,
+    };
     use crate::plist::Plist;
+    use chrono::{DateTime, Utc};
+    use std::time::{Duration, UNIX_EPOCH};
 
     #[test]
     fn test_parse() {
@@ -257,13 +614,995 @@ mod plist_test {
 </dict>
 </plist>
     "#;
-        let mut value = Plist::parse(xml.as_bytes()).unwrap();
-        if let Plist::Dictionary(dict) = &mut value {
-            if let Some(Plist::Boolean(value)) = dict.get("hello") {
-                assert_eq!(*value, true);
-            }
+        let value = Plist::parse(xml.as_bytes()).unwrap();
+        if let Some(Plist::Boolean(value)) = value.get("hello") {
+            assert!(*value);
         }
         // value.sort_key();
-        println!("{}", value.to_xml());
+        println!("{}", value.to_xml().unwrap());
+    }
+
+    #[test]
+    fn test_data_base64() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0">
+<dict>
+	<key>Certificate</key>
+	<data>
+	MIIFyTCCBLGgAwIBAgIQQ1PQwRY7PCEtVwuLsjLWtTANBgkqhkiG9w0BAQsFADB1
+	MUQwQgYDVQQDDDtBcHBsZSBXb3JsZHdpZGUgRGV2ZWxvcGVyIFJlbGF0aW9ucyBD
+	</data>
+	<key>Short</key>
+	<data>AAEC/w==</data>
+	<key>Empty</key>
+	<data/>
+</dict>
+</plist>"#;
+        let value = Plist::parse(xml.as_bytes()).unwrap();
+        match value.get("Certificate") {
+            Some(Plist::Data(data)) => {
+                assert_eq!(data.len(), 96);
+                assert_eq!(&data[..4], &[0x30, 0x82, 0x05, 0xC9]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(value.get("Short"), Some(&Plist::Data(vec![0, 1, 2, 255])));
+        assert_eq!(value.get("Empty"), Some(&Plist::Data(vec![])));
+
+        let xml = value.to_xml().unwrap();
+        assert!(xml.contains("AAEC/w=="));
+        assert_eq!(Plist::parse(xml.as_bytes()).unwrap(), value);
+        let binary = value.to_binary().unwrap();
+        assert_eq!(Plist::parse(&binary).unwrap(), value);
+    }
+
+    #[test]
+    fn test_data_invalid_base64() {
+        let xml = r#"<plist version="1.0"><dict><key>a</key><data>AA$A</data></dict></plist>"#;
+        assert!(Plist::parse(xml.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_entities() {
+        let xml = r#"<plist version="1.0"><dict>
+	<key>a&lt;b</key>
+	<string>Tom &amp; Jerry &quot;&apos;&gt; &#65;&#x42;&#x1F600;</string>
+</dict></plist>"#;
+        let value = Plist::parse(xml.as_bytes()).unwrap();
+        assert_eq!(
+            value.get("a<b"),
+            Some(&Plist::from("Tom & Jerry \"'> AB\u{1F600}"))
+        );
+
+        let mut value = Plist::Dictionary(Default::default());
+        value.insert("Tom & Jerry", "a<b>&c".into()).unwrap();
+        value.insert("<key>", "&amp;".into()).unwrap();
+        let xml = value.to_xml().unwrap();
+        assert!(xml.contains("<key>Tom &amp; Jerry</key>"));
+        assert!(xml.contains("<string>a&lt;b&gt;&amp;c</string>"));
+        assert_eq!(Plist::parse(xml.as_bytes()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_unknown_entity() {
+        let xml =
+            r#"<plist version="1.0"><dict><key>a</key><string>&nbsp;</string></dict></plist>"#;
+        assert!(Plist::parse(xml.as_bytes()).is_err());
+        let xml = r#"<plist version="1.0"><dict><key>a</key><string>a & b</string></dict></plist>"#;
+        assert!(Plist::parse(xml.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_xml_error_positions() {
+        let error = |xml: &str| match Plist::parse(xml.as_bytes()) {
+            Err(Error::Xml { kind, line, column }) => (kind, line, column),
+            other => panic!("unexpected result {:?}", other),
+        };
+        let xml = "<plist>\n<dict>\n\t<key>a</key>\n\t<data>!!</data>\n</dict>\n</plist>";
+        assert_eq!(error(xml), (ErrorKind::BadData, 4, 8));
+        let xml = "<plist>\n<dict>\n\t<key>a</key>\n\t<strin>b</strin>\n</dict>\n</plist>";
+        assert_eq!(error(xml), (ErrorKind::UnknownTag("strin".into()), 4, 2));
+        //<plist> 之前的空行也计入行号
+        let xml = format!("\n\n\n{}", xml);
+        assert_eq!(error(&xml), (ErrorKind::UnknownTag("strin".into()), 7, 2));
+        let xml = "<plist><dict><key>a</key><array><integer>1x</integer></array></dict></plist>";
+        assert_eq!(error(xml), (ErrorKind::BadInteger("1x".into()), 1, 42));
+        let xml = "<plist><dict><key>a&bogus;</key><true/></dict></plist>";
+        assert_eq!(error(xml), (ErrorKind::BadEntity("&bogus;".into()), 1, 19));
+        let xml = "<plist><dict><key>a</key><string>b";
+        assert_eq!(error(xml).0, ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_invalid_real_and_date() {
+        let parse = |value: &str| {
+            let xml = format!("<plist><dict><key>a</key>{}</dict></plist>", value);
+            Plist::parse(xml.as_bytes()).map(|plist| plist.get("a").unwrap().clone())
+        };
+        let error = |value: &str| parse(value).unwrap_err().kind().cloned();
+        assert_eq!(
+            error("<real>abc</real>"),
+            Some(ErrorKind::BadReal("abc".into()))
+        );
+        assert_eq!(
+            error("<date>2024-13-45</date>"),
+            Some(ErrorKind::BadDate("2024-13-45".into()))
+        );
+        assert!(matches!(
+            parse("<real>1.5x</real>"),
+            Err(Error::Xml {
+                line: 1,
+                column: 32,
+                ..
+            })
+        ));
+        assert!(matches!(parse("<real>nan</real>"), Ok(Plist::Float(v)) if v.value().is_nan()));
+        assert_eq!(
+            parse("<real>-infinity</real>").unwrap(),
+            Plist::from(f64::NEG_INFINITY)
+        );
+        assert_eq!(parse("<real> 2.5 </real>").unwrap(), Plist::from(2.5));
+        //非 Z 结尾的日期
+        let expected = Plist::Date("2024-08-17T02:24:50Z".parse().unwrap());
+        for date in [
+            "2024-08-17T02:24:50Z",
+            "2024-08-17T02:24:50",
+            "2024-08-17T10:24:50+08:00",
+            "2024-08-17T02:24:50.000Z",
+        ] {
+            assert_eq!(parse(&format!("<date>{}</date>", date)).unwrap(), expected);
+        }
+        assert_eq!(
+            parse("<date>2024-08-17</date>").unwrap(),
+            Plist::Date("2024-08-17T00:00:00Z".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_xml_root_types() {
+        let parse = |body: &str| {
+            let xml = format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<plist version=\"1.0\">\n{}\n</plist>\n",
+                body
+            );
+            Plist::parse(xml.as_bytes()).unwrap()
+        };
+        assert_eq!(
+            parse("<array>\n\t<string>a</string>\n\t<integer>1</integer>\n</array>"),
+            Plist::Array(vec!["a".into(), Plist::from(1_i64)])
+        );
+        assert_eq!(parse("<array/>"), Plist::Array(vec![]));
+        assert_eq!(parse("<string>hello</string>"), Plist::from("hello"));
+        assert_eq!(parse("<integer>-7</integer>"), Plist::from(-7_i64));
+        assert_eq!(parse("<true/>"), Plist::Boolean(true));
+        assert_eq!(parse("<data>AAE=</data>"), Plist::Data(vec![0, 1]));
+
+        let value = Plist::Array(vec![Plist::from(1.5_f64), Plist::Array(vec![])]);
+        assert_eq!(
+            Plist::parse(value.to_xml().unwrap().as_bytes()).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn test_comments_and_cdata() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!-- 手工编辑的文件 -->
+<plist version="1.0">
+<!-- root -->
+<dict>
+	<!-- <key>disabled</key> -->
+	<key>na<!-- x -->me</key>
+	<?editor note?>
+	<string><![CDATA[a <b> & c]]> &amp; d<![CDATA[]]></string>
+	<key>list</key>
+	<array>
+		<!-- first -->
+		<integer>1</integer>
+		<?pi?>
+		<true/>
+		<!-- last -->
+	</array>
+	<!-- end -->
+</dict>
+<!-- trailing -->
+</plist>
+"#;
+        let plist = Plist::parse(xml.as_bytes()).unwrap();
+        assert_eq!(plist.get("name"), Some(&Plist::from("a <b> & c & d")));
+        assert_eq!(
+            plist.get("list"),
+            Some(&Plist::Array(vec![
+                Plist::from(1_i64),
+                Plist::Boolean(true)
+            ]))
+        );
+        assert_eq!(plist.get("disabled"), None);
+    }
+
+    #[test]
+    fn test_xml_tokenizer() {
+        let xml = r#"<?xml version='1.0' encoding='UTF-8'?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd" [
+  <!ENTITY unused "x">
+]>
+<plist version='1.0' xmlns:x="urn:x">
+<dict >
+	<key>a &lt; b</key>
+	<true />
+	<key id="k">flag</key>
+	<false></false>
+	<key>list</key>
+	<array
+		>
+		<string xml:space="preserve">x &gt; y</string>
+		<integer> 42 </integer>
+		<data
+		/>
+	</array >
+</dict>
+</plist >
+"#;
+        let plist = Plist::parse(xml.as_bytes()).unwrap();
+        assert_eq!(plist.get("a < b"), Some(&Plist::Boolean(true)));
+        assert_eq!(plist.get("flag"), Some(&Plist::Boolean(false)));
+        assert_eq!(
+            plist.get("list"),
+            Some(&Plist::Array(vec![
+                "x > y".into(),
+                Plist::from(42_i64),
+                Plist::Data(vec![])
+            ]))
+        );
+
+        let error = |xml: &str| Plist::parse(xml.as_bytes()).unwrap_err().kind().cloned();
+        let invalid = Some(ErrorKind::InvalidSyntax);
+        assert_eq!(error("<plist><array></dict></plist>"), invalid);
+        assert_eq!(
+            error("<plist><dict><string>a</string></dict></plist>"),
+            invalid
+        );
+        assert_eq!(error("<plist><array>text</array></plist>"), invalid);
+        assert_eq!(error("<plist><true>yes</true></plist>"), invalid);
+        assert_eq!(error("<plist><string a=b>x</string></plist>"), invalid);
+        assert_eq!(
+            error("<plist><array><true/>"),
+            Some(ErrorKind::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn test_xml_encodings() {
+        let xml = "<?xml version=\"1.0\" encoding=\"UTF-16\"?>\n<plist version=\"1.0\">\n<dict>\n\t<key>名称</key>\n\t<string>Größe 😀</string>\n</dict>\n</plist>\n";
+        let expected = Plist::parse(xml.as_bytes()).unwrap();
+        assert_eq!(expected.get("名称"), Some(&Plist::from("Größe 😀")));
+        let utf16le: Vec<u8> = xml.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let utf16be: Vec<u8> = xml.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        for data in [
+            [&[0xFF, 0xFE], utf16le.as_slice()].concat(),
+            [&[0xFE, 0xFF], utf16be.as_slice()].concat(),
+            utf16le.clone(),
+            utf16be.clone(),
+            [b"\xEF\xBB\xBF", xml.as_bytes()].concat(),
+        ] {
+            assert_eq!(Plist::parse(&data).unwrap(), expected);
+        }
+
+        let latin1 = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><plist><string>caf\xE9</string></plist>";
+        assert_eq!(Plist::parse(latin1).unwrap(), Plist::from("café"));
+        //非严格模式从前后的二进制数据中提取 plist, 例如描述文件的签名数据; 严格模式不提取
+        let signed = [b"0\x82\xFF\x00", xml.as_bytes(), b"\xA0\x82\xFF"].concat();
+        assert_eq!(Plist::parse(&signed).unwrap(), expected);
+        let strict = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            Plist::parse_with(&signed, &strict),
+            Err(Error::Xml {
+                kind: ErrorKind::InvalidUtf8,
+                line: 1,
+                column: 2
+            })
+        ));
+        //提取出的文档按自己的编码声明解码, 错误位置按完整的输入计算
+        let signed = [b"0\x82\xFF\x00", latin1.as_slice(), b"\xA0\x82\xFF"].concat();
+        assert_eq!(Plist::parse(&signed).unwrap(), Plist::from("café"));
+        let broken = [
+            b"0\x82\n\xFF\x00".as_slice(),
+            b"<plist>\n<array></dict></plist>",
+            b"\xA0\x82",
+        ]
+        .concat();
+        assert!(matches!(
+            Plist::parse(&broken),
+            Err(Error::Xml {
+                kind: ErrorKind::InvalidSyntax,
+                line: 3,
+                column: 8
+            })
+        ));
+
+        let error = |data: &[u8]| match Plist::parse(data) {
+            Err(Error::Xml { kind, line, column }) => (kind, line, column),
+            other => panic!("unexpected result {:?}", other),
+        };
+        assert_eq!(
+            error(b"<?xml version=\"1.0\" encoding=\"Shift_JIS\"?><plist><true/></plist>"),
+            (ErrorKind::UnsupportedEncoding("Shift_JIS".into()), 1, 31)
+        );
+        assert_eq!(
+            error(b"<plist>\n<string>a\xFFb</string></plist>"),
+            (ErrorKind::InvalidUtf8, 2, 10)
+        );
+        let mut broken = utf16le.clone();
+        broken[10..12].copy_from_slice(&0xD800_u16.to_le_bytes());
+        assert_eq!(error(&broken), (ErrorKind::InvalidUtf16, 1, 6));
+        assert_eq!(
+            error(&utf16le[..utf16le.len() - 1]).0,
+            ErrorKind::InvalidUtf16
+        );
+    }
+
+    #[test]
+    fn test_string_whitespace() {
+        let samples = [
+            "",
+            " ",
+            "   ",
+            "\t",
+            "\n",
+            "\n\n",
+            " \t\n ",
+            "  leading",
+            "trailing  ",
+            "\tboth sides\t",
+            "\n\tline one\n\tline two\n",
+            "windows\r\nline\r",
+            " <tag> & ",
+        ];
+        let mut dict = Plist::Dictionary(Default::default());
+        for (index, sample) in samples.iter().enumerate() {
+            dict.insert(&format!(" key {} ", index), Plist::from(*sample))
+                .unwrap();
+        }
+        dict.insert(
+            "list",
+            Plist::Array(samples.iter().map(|s| Plist::from(*s)).collect()),
+        )
+        .unwrap();
+        let parsed = Plist::parse(dict.to_xml().unwrap().as_bytes()).unwrap();
+        assert_eq!(parsed, dict);
+
+        let xml = "<plist><array>\n\t<string>  a  </string>\n\t<string>\n</string>\n\t<string>\r\n</string>\n\t<string><![CDATA[ b ]]></string>\n</array></plist>";
+        assert_eq!(
+            Plist::parse(xml.as_bytes()).unwrap(),
+            Plist::Array(vec!["  a  ".into(), "\n".into(), "\n".into(), " b ".into()])
+        );
+    }
+
+    #[test]
+    fn test_xml_duplicate_keys() {
+        let xml = "<plist><dict>\n<key>k</key><true/>\n<key>k</key><false/>\n</dict></plist>";
+        let parse = |duplicate_keys| {
+            let options = ParseOptions {
+                duplicate_keys,
+                ..Default::default()
+            };
+            Plist::parse_with(xml.as_bytes(), &options)
+        };
+        let mut expected = Plist::Dictionary(Default::default());
+        expected.insert("k", Plist::Boolean(false)).unwrap();
+        assert_eq!(parse(DuplicateKeys::KeepLast).unwrap(), expected);
+        expected.insert("k", Plist::Boolean(true)).unwrap();
+        assert_eq!(parse(DuplicateKeys::KeepFirst).unwrap(), expected);
+        assert!(matches!(
+            parse(DuplicateKeys::Error),
+            Err(Error::Xml {
+                kind: ErrorKind::DuplicateKey(key),
+                line: 3,
+                column: 1
+            }) if key == "k"
+        ));
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let parse = |xml: &str, options: &ParseOptions| Plist::parse_with(xml.as_bytes(), options);
+        let nested = format!(
+            "<plist>{}{}</plist>",
+            "<array>".repeat(100),
+            "</array>".repeat(100)
+        );
+        assert!(parse(&nested, &ParseOptions::default()).is_ok());
+        let options = ParseOptions {
+            max_depth: 50,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse(&nested, &options).unwrap_err().kind(),
+            Some(&ErrorKind::DepthLimitExceeded(50))
+        );
+        //默认最大深度的字典和数组嵌套在 2 MB 的线程栈上也不会溢出
+        let half = ParseOptions::default().max_depth / 2;
+        let nested = format!(
+            "<plist>{}<true/>{}</plist>",
+            "<array><dict><key>k</key>".repeat(half),
+            "</dict></array>".repeat(half)
+        );
+        let handle = std::thread::Builder::new()
+            .stack_size(2 << 20)
+            .spawn(move || Plist::parse(nested.as_bytes()).is_ok())
+            .unwrap();
+        assert!(handle.join().unwrap());
+        let options = ParseOptions {
+            max_objects: Some(10),
+            ..Default::default()
+        };
+        let list = format!("<plist><array>{}</array></plist>", "<true/>".repeat(10));
+        assert_eq!(
+            parse(&list, &options).unwrap_err().kind(),
+            Some(&ErrorKind::ObjectLimitExceeded(10))
+        );
+
+        //严格模式不允许 <plist> 前后出现其他内容
+        let strict = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        let xml = "<?xml version=\"1.0\"?>\n<!-- c -->\n<plist><true/></plist>\n<!-- end -->\n";
+        assert_eq!(parse(xml, &strict).unwrap(), Plist::Boolean(true));
+        for xml in ["junk<plist><true/></plist>", "<plist><true/></plist>junk"] {
+            assert!(parse(xml, &ParseOptions::default()).is_ok());
+            assert_eq!(
+                parse(xml, &strict).unwrap_err().kind(),
+                Some(&ErrorKind::InvalidSyntax)
+            );
+        }
+
+        let strict_dates = ParseOptions {
+            lenient_dates: false,
+            ..Default::default()
+        };
+        let xml = "<plist><date>2024-08-17T02:24:50Z</date></plist>";
+        assert!(parse(xml, &strict_dates).is_ok());
+        let xml = "<plist><date>2024-08-17T02:24:50</date></plist>";
+        assert!(parse(xml, &ParseOptions::default()).is_ok());
+        assert!(matches!(
+            parse(xml, &strict_dates).unwrap_err().kind(),
+            Some(ErrorKind::BadDate(_))
+        ));
+
+        let xml = "<plist><dict><key>CF$UID</key><integer>3</integer></dict></plist>";
+        assert!(matches!(
+            parse(xml, &ParseOptions::default()).unwrap(),
+            Plist::Dictionary(_)
+        ));
+        let options = ParseOptions {
+            recover_uids: true,
+            ..Default::default()
+        };
+        assert_eq!(parse(xml, &options).unwrap(), Plist::Uid(3));
+    }
+
+    #[test]
+    fn test_write_xml() {
+        let mut value = Plist::Dictionary(Default::default());
+        value.insert("name", "a & b".into()).unwrap();
+        value.insert("data", vec![1_u8, 2, 3].into()).unwrap();
+        value
+            .insert("list", Plist::Array(vec![true.into(), Plist::from(-1_i64)]))
+            .unwrap();
+        value
+            .insert("empty", Plist::Dictionary(Default::default()))
+            .unwrap();
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>name</key>
+	<string>a &amp; b</string>
+	<key>data</key>
+	<data>
+	AQID
+	</data>
+	<key>list</key>
+	<array>
+		<true/>
+		<integer>-1</integer>
+	</array>
+	<key>empty</key>
+	<dict/>
+</dict>
+</plist>"#;
+        let mut output = vec![];
+        value.write_xml(&mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), expected);
+        assert_eq!(value.to_xml().unwrap(), expected);
+
+        //写入失败时返回 IoError
+        struct Broken;
+        impl std::io::Write for Broken {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        assert!(matches!(value.write_xml(Broken), Err(Error::IoError(_))));
+    }
+
+    #[test]
+    fn test_write_xml_depth() {
+        //读取允许的最大深度写成 XML 时, 在 2 MB 的线程栈上也不会溢出
+        let depth = ParseOptions::default().max_depth;
+        let mut value = Plist::Array(vec![]);
+        for level in 1..depth {
+            value = if level % 2 == 0 {
+                Plist::Array(vec![value])
+            } else {
+                let mut dict = Plist::Dictionary(Default::default());
+                dict.insert("k", value).unwrap();
+                dict
+            };
+        }
+        let handle = std::thread::Builder::new()
+            .stack_size(2 << 20)
+            .spawn(move || {
+                let xml = value.to_xml().unwrap();
+                let apple = value.to_xml_with(&XmlWriteOptions::apple_style()).unwrap();
+                (xml, apple)
+            })
+            .unwrap();
+        let (xml, apple) = handle.join().unwrap();
+        let containers = |xml: &str| xml.matches("<array").count() + xml.matches("<dict>").count();
+        assert_eq!(containers(&xml), depth);
+        assert_eq!(containers(&apple), depth);
+    }
+
+    #[test]
+    fn test_xml_write_options() {
+        let mut value = Plist::Dictionary(Default::default());
+        value
+            .insert("list", Plist::Array(vec!["a".into(), vec![1_u8].into()]))
+            .unwrap();
+        let options = XmlWriteOptions {
+            indent: "  ".to_string(),
+            doctype: false,
+            trailing_newline: true,
+            ..Default::default()
+        };
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0">
+<dict>
+  <key>list</key>
+  <array>
+    <string>a</string>
+    <data>
+    AQ==
+    </data>
+  </array>
+</dict>
+</plist>
+"#;
+        assert_eq!(value.to_xml_with(&options).unwrap(), expected);
+
+        let options = XmlWriteOptions {
+            declaration: false,
+            doctype: false,
+            compact: true,
+            ..Default::default()
+        };
+        let expected = r#"<plist version="1.0"><dict><key>list</key><array><string>a</string><data>AQ==</data></array></dict></plist>"#;
+        assert_eq!(value.to_xml_with(&options).unwrap(), expected);
+        let mut output = vec![];
+        value.write_xml_with(&mut output, &options).unwrap();
+        assert_eq!(output, expected.as_bytes());
+        assert_eq!(Plist::parse(expected.as_bytes()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_apple_style_xml() {
+        //快照是本库 apple_style 的输出, 只用于发现输出的变化, 不是 plutil 的输出
+        let options = XmlWriteOptions::apple_style();
+        for name in ["Info.plist", "InfoPlist.strings"] {
+            let data = std::fs::read(format!("./data/{}", name)).unwrap();
+            let snapshot =
+                std::fs::read_to_string(format!("./data/snapshot/{}.xml", name)).unwrap();
+            assert_eq!(
+                Plist::parse(&data).unwrap().to_xml_with(&options).unwrap(),
+                snapshot
+            );
+        }
+        //重新解析后写出的结果不变
+        for name in ["Info.plist", "InfoPlist.strings", "edge", "uniquing"] {
+            let snapshot =
+                std::fs::read_to_string(format!("./data/snapshot/{}.xml", name)).unwrap();
+            let value = Plist::parse(snapshot.as_bytes()).unwrap();
+            assert_eq!(value.to_xml_with(&options).unwrap(), snapshot);
+            let mut output = vec![];
+            value.write_xml_with(&mut output, &options).unwrap();
+            assert_eq!(output, snapshot.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_apple_style_binary() {
+        //Apple 生成的二进制文件重新写出后逐字节一致
+        let options = BinaryWriteOptions::apple_style();
+        for name in ["Info.plist", "InfoPlist.strings"] {
+            let data = std::fs::read(format!("./data/{}", name)).unwrap();
+            let value = Plist::parse(&data).unwrap();
+            assert_eq!(value.to_binary_with(&options).unwrap(), data);
+        }
+
+        //布尔值重复写出但只引用第一个, UID 不去重, 实数固定 8 字节
+        let value = Plist::Array(vec![
+            true.into(),
+            true.into(),
+            Plist::Uid(1),
+            Plist::Uid(1),
+            Plist::from(1.5_f64),
+        ]);
+        let binary = value.to_binary_with(&options).unwrap();
+        assert_eq!(&binary[8..14], &[0xA5, 1, 1, 3, 4, 5]);
+        assert_eq!(&binary[14..20], &[0x09, 0x09, 0x80, 1, 0x80, 1]);
+        assert_eq!(binary[20], 0x23);
+        assert_eq!(Plist::parse(&binary).unwrap(), value);
+    }
+
+    #[test]
+    fn test_real_precision() {
+        //二进制中的实数按原来的宽度写回
+        let value = Plist::Array(vec![
+            Plist::from(2.5_f32),
+            Plist::from(2.5_f64),
+            Plist::from(0.1_f32),
+        ]);
+        let binary = value.to_binary().unwrap();
+        assert_eq!(&binary[8..12], &[0xA3, 1, 2, 3]);
+        assert_eq!(binary[12], 0x22);
+        assert_eq!(binary[17], 0x23);
+        assert_eq!(binary[26], 0x22);
+        let parsed = Plist::parse(&binary).unwrap();
+        let Plist::Array(list) = &parsed else {
+            panic!("expected array");
+        };
+        let widths: Vec<bool> = list
+            .iter()
+            .map(|item| matches!(item, Plist::Float(real) if real.is_single()))
+            .collect();
+        assert_eq!(widths, [true, false, true]);
+        assert_eq!(parsed.to_binary().unwrap(), binary);
+        //apple_style 同样保留宽度, 宽度不同的实数不会合并
+        let apple = parsed
+            .to_binary_with(&BinaryWriteOptions::apple_style())
+            .unwrap();
+        assert_eq!(apple, binary);
+
+        //XML 中的写法只在开启 keep_real_text 时保留
+        let xml = r#"<plist version="1.0"><array><real>1.50</real><real> 1e3 </real><real>.5</real></array></plist>"#;
+        let options = XmlWriteOptions {
+            declaration: false,
+            doctype: false,
+            compact: true,
+            ..Default::default()
+        };
+        let parsed = Plist::parse(xml.as_bytes()).unwrap();
+        let expected = r#"<plist version="1.0"><array><real>1.5</real><real>1000</real><real>0.5</real></array></plist>"#;
+        assert_eq!(parsed.to_xml_with(&options).unwrap(), expected);
+        let parse_options = ParseOptions {
+            keep_real_text: true,
+            ..Default::default()
+        };
+        let kept = Plist::parse_with(xml.as_bytes(), &parse_options).unwrap();
+        let expected = r#"<plist version="1.0"><array><real>1.50</real><real>1e3</real><real>.5</real></array></plist>"#;
+        assert_eq!(kept.to_xml_with(&options).unwrap(), expected);
+        assert_eq!(kept, parsed);
+        //apple_style 始终按 %.17g 输出
+        let apple = XmlWriteOptions {
+            compact: true,
+            ..XmlWriteOptions::apple_style()
+        };
+        assert!(
+            kept.to_xml_with(&apple)
+                .unwrap()
+                .contains("<real>1.5</real><real>1000</real>")
+        );
+    }
+
+    #[test]
+    fn test_date_fidelity() {
+        let date = Date::from_seconds_since_2001;
+        assert_eq!(date(0.0).to_string(), "2001-01-01T00:00:00Z");
+        assert_eq!(date(-0.25).to_string(), "2000-12-31T23:59:59.75Z");
+        assert_eq!(date(1e-7).to_string(), "2001-01-01T00:00:00.0000001Z");
+        assert_eq!(
+            date(-1e-20).to_string(),
+            "2000-12-31T23:59:59.99999999999999999999Z"
+        );
+        assert_eq!(date(-63_113_904_000.0).to_string(), "0001-01-01T00:00:00Z");
+        assert_eq!(date(-63_145_526_400.0).to_string(), "0000-01-01T00:00:00Z");
+        assert_eq!(date(-63_208_598_400.0).to_string(), "-002-01-01T00:00:00Z");
+        assert_eq!(date(252_423_993_600.0).to_string(), "10000-01-01T00:00:00Z");
+        for text in [
+            "2000-02-29T12:00:00Z",
+            "0000-02-29T00:00:00Z",
+            "-001-12-31T23:59:59.5Z",
+        ] {
+            assert_eq!(text.parse::<Date>().unwrap().to_string(), text);
+        }
+        for text in [
+            "2001-02-29T00:00:00Z",
+            "2001-01-01T24:00:00Z",
+            "2001-1-01T00:00:00Z",
+        ] {
+            assert!(text.parse::<Date>().is_err());
+        }
+
+        //远古、远期和带小数的日期在两种格式中都能精确往返
+        let values = [
+            0.0,
+            0.5,
+            -0.25,
+            1e-7,
+            537_734_706.123_456_7,
+            -978_307_200.000_001,
+            -63_113_904_000.5,
+            1e15 + 0.5,
+            -1e15,
+            9.2e18,
+            -1e-20,
+            -5e-324,
+            -1e15 - 0.5,
+        ];
+        let value = Plist::Array(values.iter().map(|v| date(*v).into()).collect());
+        let xml = Plist::parse(value.to_xml().unwrap().as_bytes()).unwrap();
+        let binary = Plist::parse(&value.to_binary().unwrap()).unwrap();
+        for parsed in [xml, binary] {
+            let Plist::Array(list) = parsed else {
+                panic!("expected array");
+            };
+            let seconds: Vec<u64> = list
+                .iter()
+                .map(|item| match item {
+                    Plist::Date(date) => date.seconds_since_2001().to_bits(),
+                    _ => panic!("expected date"),
+                })
+                .collect();
+            let expected: Vec<u64> = values.iter().map(|v| v.to_bits()).collect();
+            assert_eq!(seconds, expected);
+        }
+        //apple_style 只写整秒
+        let options = XmlWriteOptions {
+            compact: true,
+            ..XmlWriteOptions::apple_style()
+        };
+        let xml = Plist::from(date(-0.25)).to_xml_with(&options).unwrap();
+        assert!(xml.contains("<date>2000-12-31T23:59:59Z</date>"));
+
+        //NaN 和超出范围的日期在二进制中原样保留, 无法写成 XML
+        let value = Plist::Array(vec![date(f64::NAN).into(), date(1e300).into()]);
+        let binary = value.to_binary().unwrap();
+        let Plist::Array(list) = Plist::parse(&binary).unwrap() else {
+            panic!("expected array");
+        };
+        assert!(matches!(&list[0], Plist::Date(d) if d.seconds_since_2001().is_nan()));
+        assert_eq!(list[1], date(1e300).into());
+        assert!(value.write_xml(&mut vec![]).is_err());
+        //夹在其他值之间时也不会输出残缺的 XML
+        let value = Plist::Array(vec!["before".into(), date(f64::NAN).into(), "after".into()]);
+        assert!(value.to_bytes(b"<?xml").is_err());
+        assert!(value.to_xml().is_err());
+        assert!(value.to_bytes(b"bplist00").is_ok());
+        assert_eq!(date(1e300).to_date_time(), None);
+
+        //按需转换成 chrono 和 SystemTime
+        let chrono: DateTime<Utc> = "2018-01-15T18:18:26.250Z".parse().unwrap();
+        let converted = Date::from(chrono);
+        assert_eq!(converted.seconds_since_2001(), 537_733_106.25);
+        assert_eq!(converted.to_date_time(), Some(chrono));
+        assert_eq!(
+            date(0.0).to_system_time(),
+            Some(UNIX_EPOCH + Duration::from_secs(978_307_200))
+        );
+        assert_eq!(Date::from(UNIX_EPOCH).seconds_since_2001(), -978_307_200.0);
+    }
+
+    #[test]
+    fn test_uid() {
+        let value = Plist::Array(vec![
+            Plist::Uid(0),
+            Plist::Uid(300),
+            Plist::Uid(70000),
+            Plist::Uid(1 << 40),
+            Plist::from(5_i64),
+            Plist::Uid(5),
+        ]);
+        let binary = value.to_binary().unwrap();
+        assert_eq!(Plist::parse(&binary).unwrap(), value);
+
+        let mut value = Plist::Dictionary(Default::default());
+        value
+            .insert("root", Plist::Array(vec![Plist::Uid(7)]))
+            .unwrap();
+        let xml = value.to_xml().unwrap();
+        assert!(xml.contains(
+            "\t\t<dict>\n\t\t\t<key>CF$UID</key>\n\t\t\t<integer>7</integer>\n\t\t</dict>"
+        ));
+        let plain = Plist::parse(xml.as_bytes()).unwrap();
+        assert!(
+            matches!(plain.get("root"), Some(Plist::Array(list)) if matches!(list[0], Plist::Dictionary(_)))
+        );
+        let archive = Plist::parse_keyed_archive(xml.as_bytes()).unwrap();
+        assert_eq!(archive, value);
+    }
+
+    #[test]
+    fn test_uid_wide() {
+        //单个 16 字节 UID 对象
+        let mut data = b"bplist00".to_vec();
+        data.push(0x8F);
+        data.extend([0; 15]);
+        data.push(42);
+        data.push(8);
+        data.extend([0, 0, 0, 0, 0, 0, 1, 1]);
+        data.extend(1_u64.to_be_bytes());
+        data.extend(0_u64.to_be_bytes());
+        data.extend(25_u64.to_be_bytes());
+        assert_eq!(Plist::parse(&data).unwrap(), Plist::Uid(42));
+        data[9] = 1;
+        assert!(Plist::parse(&data).is_err());
+    }
+
+    #[test]
+    fn test_integer_round_trip() {
+        let values = [
+            i64::MIN,
+            i64::MIN + 1,
+            -4294967297,
+            -65536,
+            -256,
+            -1,
+            0,
+            1,
+            255,
+            256,
+            65535,
+            65536,
+            4294967295,
+            4294967296,
+            i64::MAX - 1,
+            i64::MAX,
+        ];
+        let value = Plist::Array(values.iter().map(|v| Plist::from(*v)).collect());
+        let binary = value.to_binary().unwrap();
+        assert_eq!(Plist::parse(&binary).unwrap(), value);
+        for v in values {
+            let binary = Plist::Array(vec![Plist::from(v)]).to_binary().unwrap();
+            assert_eq!(
+                Plist::parse(&binary).unwrap(),
+                Plist::Array(vec![Plist::from(v)])
+            );
+        }
+    }
+
+    #[test]
+    fn test_unsigned_integer() {
+        let values = [
+            i64::MAX as u64 + 1,
+            0xFEDC_BA98_7654_3210,
+            u64::MAX - 1,
+            u64::MAX,
+        ];
+        let value = Plist::Array(values.iter().map(|v| Plist::from(*v)).collect());
+        let binary = value.to_binary().unwrap();
+        assert_eq!(Plist::parse(&binary).unwrap(), value);
+        if let Plist::Array(list) = Plist::parse(&binary).unwrap() {
+            assert_eq!(list[3], Plist::Integer(Integer::MAX));
+            if let Plist::Integer(v) = list[3] {
+                assert_eq!(v.as_unsigned(), Some(u64::MAX));
+                assert_eq!(v.as_signed(), None);
+            }
+        }
+
+        let mut dict = Plist::Dictionary(Default::default());
+        dict.insert("max", Plist::from(u64::MAX)).unwrap();
+        dict.insert("min", Plist::from(i64::MIN)).unwrap();
+        let xml = dict.to_xml().unwrap();
+        assert!(xml.contains("<integer>18446744073709551615</integer>"));
+        assert!(xml.contains("<integer>-9223372036854775808</integer>"));
+        assert_eq!(Plist::parse(xml.as_bytes()).unwrap(), dict);
+
+        let xml = r#"<plist version="1.0"><dict><key>a</key><integer>18446744073709551616</integer></dict></plist>"#;
+        assert!(Plist::parse(xml.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_integer_16_bytes() {
+        //0x14 标记的 16 字节整数
+        let mut data = b"bplist00".to_vec();
+        data.push(0x14);
+        data.extend([0; 8]);
+        data.extend(u64::MAX.to_be_bytes());
+        data.push(8);
+        data.extend([0, 0, 0, 0, 0, 0, 1, 1]);
+        data.extend(1_u64.to_be_bytes());
+        data.extend(0_u64.to_be_bytes());
+        data.extend(25_u64.to_be_bytes());
+        assert_eq!(Plist::parse(&data).unwrap(), Plist::from(u64::MAX));
+        data[9..17].copy_from_slice(&[0xFF; 8]);
+        data[17..25].copy_from_slice(&(-2_i64).to_be_bytes());
+        assert_eq!(Plist::parse(&data).unwrap(), Plist::from(-2_i64));
+        data[9] = 1;
+        assert!(Plist::parse(&data).is_err());
+    }
+
+    #[test]
+    fn test_binary_many_objects() {
+        let list: Vec<Plist> = (0..5000)
+            .map(|i| Plist::from(format!("string value number {}", i)))
+            .collect();
+        let value = Plist::Array(list);
+        let binary = value.to_binary().unwrap();
+        let trailer = &binary[binary.len() - 32..];
+        assert_eq!(trailer[6], 4); //偏移大小
+        assert_eq!(trailer[7], 2); //引用大小
+        assert_eq!(Plist::parse(&binary).unwrap(), value);
+
+        let mut dict = Plist::Dictionary(Default::default());
+        for i in 0..3000_i64 {
+            let item = Plist::Array(vec![
+                Plist::from(i),
+                Plist::from(format!("{}", i * 7)),
+                Plist::from(true),
+            ]);
+            dict.insert(&format!("key{}", i), item).unwrap();
+        }
+        let binary = dict.to_binary().unwrap();
+        assert_eq!(Plist::parse(&binary).unwrap(), dict);
+    }
+
+    #[test]
+    fn test_binary_deduplication() {
+        let item = Plist::Array(vec![Plist::from("shared"), Plist::from(1_i64)]);
+        let value = Plist::Array(vec![item.clone(), item.clone(), Plist::from("shared")]);
+        let count = |deduplication| {
+            let options = BinaryWriteOptions {
+                deduplication,
+                ..Default::default()
+            };
+            let binary = value.to_binary_with(&options).unwrap();
+            assert_eq!(Plist::parse(&binary).unwrap(), value);
+            let trailer = &binary[binary.len() - 32..];
+            u64::from_be_bytes(trailer[8..16].try_into().unwrap())
+        };
+        assert_eq!(count(Deduplication::None), 8);
+        assert_eq!(count(Deduplication::Scalars), 5);
+        assert_eq!(count(Deduplication::All), 4);
+    }
+
+    #[test]
+    fn test_binary_large_table() {
+        //4 万条本地化字符串, 值去重后共 41001 个对象, 引用需要 2 字节
+        let mut dict = Plist::Dictionary(Default::default());
+        for i in 0..40000 {
+            dict.insert(
+                &format!("key.{}", i),
+                Plist::from(format!("Value {}", i % 1000)),
+            )
+            .unwrap();
+        }
+        let binary = dict.to_binary().unwrap();
+        assert_eq!(binary[binary.len() - 32 + 7], 2);
+        assert_eq!(Plist::parse(&binary).unwrap(), dict);
+
+        //超过 65535 个对象时引用需要 4 字节
+        let list: Vec<Plist> = (0..70000_i64).map(Plist::from).collect();
+        let value = Plist::Array(list);
+        let binary = value.to_binary().unwrap();
+        assert_eq!(binary[binary.len() - 32 + 7], 4);
+        assert_eq!(Plist::parse(&binary).unwrap(), value);
     }
 }
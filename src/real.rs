@@ -0,0 +1,58 @@
+use std::fmt;
+
+//plist 实数, 记录二进制中的宽度 (f32/f64) 和 XML 中的原始写法, 写回时保持原来的编码
+#[derive(Debug, Clone)]
+pub struct Real {
+    value: f64,
+    single: bool,
+    text: Option<String>,
+}
+impl Real {
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+    //二进制中是 4 字节的单精度实数
+    pub fn is_single(&self) -> bool {
+        self.single
+    }
+    //XML 中 <real> 的原始写法, 只有开启 ParseOptions::keep_real_text 时才会保留
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+    pub(crate) fn with_text(mut self, text: &str) -> Self {
+        self.text = Some(text.to_string());
+        self
+    }
+}
+//宽度和写法只是编码细节, 比较时只看数值
+impl PartialEq for Real {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+impl fmt::Display for Real {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.text {
+            Some(text) => f.write_str(text),
+            None => self.value.fmt(f),
+        }
+    }
+}
+impl From<f64> for Real {
+    fn from(value: f64) -> Self {
+        Real {
+            value,
+            single: false,
+            text: None,
+        }
+    }
+}
+impl From<f32> for Real {
+    fn from(value: f32) -> Self {
+        Real {
+            value: value as f64,
+            single: true,
+            text: None,
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use crate::error::ErrorKind;
+use std::io::{self, Write};
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn decode_char(c: u8) -> Option<u8> {
+    match c {
+        b'A'..=b'Z' => Some(c - b'A'),
+        b'a'..=b'z' => Some(c - b'a' + 26),
+        b'0'..=b'9' => Some(c - b'0' + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+//3 个字节编码成 4 个字符, 不足时用 `=` 补齐
+fn encode_chunk(chunk: &[u8]) -> [u8; 4] {
+    let b0 = chunk[0] as u32;
+    let b1 = chunk.get(1).copied().unwrap_or(0) as u32;
+    let b2 = chunk.get(2).copied().unwrap_or(0) as u32;
+    let triple = (b0 << 16) | (b1 << 8) | b2;
+    let mut output = [b'='; 4];
+    output[0] = ALPHABET[(triple >> 18) as usize & 0x3F];
+    output[1] = ALPHABET[(triple >> 12) as usize & 0x3F];
+    if chunk.len() > 1 {
+        output[2] = ALPHABET[(triple >> 6) as usize & 0x3F];
+    }
+    if chunk.len() > 2 {
+        output[3] = ALPHABET[triple as usize & 0x3F];
+    }
+    output
+}
+
+//直接编码到输出流, 不生成中间字符串
+pub(crate) fn encode_to<W: Write>(data: &[u8], output: &mut W) -> io::Result<()> {
+    //每次写入 48 个字符, 减少 write 调用次数
+    for block in data.chunks(36) {
+        let mut buffer = [0_u8; 48];
+        let mut len = 0;
+        for chunk in block.chunks(3) {
+            buffer[len..len + 4].copy_from_slice(&encode_chunk(chunk));
+            len += 4;
+        }
+        output.write_all(&buffer[..len])?;
+    }
+    Ok(())
+}
+
+// 解码 base64 文本，忽略空白和换行；缺省的 `=` 补齐也可以接受
+pub(crate) fn decode(input: &str) -> Result<Vec<u8>, ErrorKind> {
+    let mut output = Vec::with_capacity(input.len() / 4 * 3);
+    let mut buffer = 0_u32;
+    let mut bits = 0;
+    let mut chars = 0_usize;
+    let mut padding = 0_usize;
+    for c in input.bytes() {
+        if c.is_ascii_whitespace() {
+            continue;
+        }
+        if c == b'=' {
+            padding += 1;
+            continue;
+        }
+        if padding > 0 {
+            return Err(ErrorKind::BadData);
+        }
+        let value = decode_char(c).ok_or(ErrorKind::BadData)?;
+        chars += 1;
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            output.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    let remainder = chars % 4;
+    if remainder == 1 {
+        return Err(ErrorKind::BadData);
+    }
+    if padding > 0 && (padding > 2 || !(chars + padding).is_multiple_of(4)) {
+        return Err(ErrorKind::BadData);
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod base64_test {
+    use super::{decode, encode_to};
+
+    fn encode(data: &[u8]) -> String {
+        let mut output = vec![];
+        encode_to(data, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"f"), "Zg==");
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(encode(b"foo"), "Zm9v");
+        assert_eq!(encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(encode(&[0xFF, 0xFE, 0x00]), "//4A");
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("").unwrap(), b"");
+        assert_eq!(decode("Zg==").unwrap(), b"f");
+        assert_eq!(decode("Zm8=").unwrap(), b"fo");
+        assert_eq!(decode("Zm9vYmFy").unwrap(), b"foobar");
+        assert_eq!(decode("\n\tZm9v\n\tYmFy\n").unwrap(), b"foobar");
+        assert_eq!(decode("Zm8").unwrap(), b"fo");
+        assert!(decode("Zm9v!").is_err());
+        assert!(decode("Z").is_err());
+        assert!(decode("Zg==Zg==").is_err());
+        assert!(decode("Zm9v=").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data: Vec<u8> = (0..=255).collect();
+        for len in 0..data.len() {
+            assert_eq!(decode(&encode(&data[..len])).unwrap(), &data[..len]);
+        }
+    }
+}
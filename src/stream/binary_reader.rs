@@ -1,12 +1,16 @@
+use crate::date::Date;
+use crate::error::{Error, ErrorKind};
+use crate::integer::Integer;
+use crate::options::{DuplicateKeys, ParseOptions};
 use crate::plist::Plist;
-use chrono::{DateTime, Utc};
 use indexmap::IndexMap;
 use nom::IResult;
 use nom::Parser;
 use nom::bytes::complete::{tag, take};
-use nom::combinator::{map, recognize};
+use nom::combinator::map;
+use nom::error::ParseError;
 use nom::multi::count;
-use nom::number::complete::{be_f32, be_f64, be_u8, be_u16, be_u32, be_u64};
+use nom::number::complete::{be_f32, be_f64, be_i64, be_i128, be_u8, be_u16, be_u32, be_u64};
 
 #[derive(Debug)]
 struct Trailer {
@@ -16,15 +20,48 @@ struct Trailer {
     top_object_offset: u64,
     offset_table_start: u64,
 }
+//nom 解析错误, 记录出错位置和错误类型
 #[derive(Debug)]
-pub struct BinaryReader {}
-impl BinaryReader {
-    fn parse_bplist_header(input: &[u8]) -> IResult<&[u8], ()> {
+struct BinaryError<'a> {
+    input: &'a [u8],
+    kind: ErrorKind,
+}
+impl<'a> ParseError<&'a [u8]> for BinaryError<'a> {
+    fn from_error_kind(input: &'a [u8], kind: nom::error::ErrorKind) -> Self {
+        let kind = match kind {
+            nom::error::ErrorKind::Tag => ErrorKind::InvalidHeader,
+            _ => ErrorKind::UnexpectedEof,
+        };
+        BinaryError { input, kind }
+    }
+    fn append(_: &'a [u8], _: nom::error::ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+type BinaryResult<'a, T> = IResult<&'a [u8], T, BinaryError<'a>>;
+
+fn fail<T>(input: &[u8], kind: ErrorKind) -> BinaryResult<'_, T> {
+    Err(nom::Err::Failure(BinaryError { input, kind }))
+}
+
+#[derive(Debug)]
+pub struct BinaryReader<'a> {
+    data: &'a [u8],
+    trailer: Trailer,
+    offsets: Vec<u64>,
+    options: ParseOptions,
+    max_objects: usize,
+    in_path: Vec<bool>, // 当前解析路径上的容器对象
+    depth: usize,
+    objects: usize, // 已解析的对象数, 共享对象重复计数
+}
+impl<'a> BinaryReader<'a> {
+    fn parse_bplist_header(input: &[u8]) -> BinaryResult<'_, ()> {
         let (input, _) = tag("bplist00").parse(input)?;
         Ok((input, ()))
     }
     //解析尾部信息
-    fn parse_trailer(input: &[u8]) -> IResult<&[u8], Trailer> {
+    fn parse_trailer(input: &[u8]) -> BinaryResult<'_, Trailer> {
         let (
             input,
             (
@@ -58,230 +95,322 @@ impl BinaryReader {
         ))
     }
     //解析对象头
-    fn parse_header(input: &[u8]) -> IResult<&[u8], (u8, u8)> {
+    fn parse_header(input: &[u8]) -> BinaryResult<'_, (u8, u8)> {
         let (input, header) = be_u8.parse(input)?;
         let object_type = (header >> 4) & 0x0F;
         let extra_info = header & 0x0F;
         Ok((input, (object_type, extra_info)))
     }
-    fn parse_integer(input: &[u8], extra_info: u8) -> IResult<&[u8], Plist> {
+    fn parse_integer(input: &[u8], extra_info: u8) -> BinaryResult<'_, Plist> {
         let size = 1 << extra_info;
         match size {
-            1 => map(be_u8, |v| Plist::Integer(v as i64)).parse(input),
-            2 => map(be_u16, |v| Plist::Integer(v as i64)).parse(input),
-            4 => map(be_u32, |v| Plist::Integer(v as i64)).parse(input),
-            8 => map(be_u64, |v| Plist::Integer(v as i64)).parse(input),
-            _ => Err(nom::Err::Error(nom::error::Error::new(
-                input,
-                nom::error::ErrorKind::Switch,
-            ))),
+            1 => map(be_u8, |v| Plist::Integer(v.into())).parse(input),
+            2 => map(be_u16, |v| Plist::Integer(v.into())).parse(input),
+            4 => map(be_u32, |v| Plist::Integer(v.into())).parse(input),
+            8 => map(be_i64, |v| Plist::Integer(v.into())).parse(input),
+            //16 字节整数用于超出 i64 范围的无符号数
+            16 => {
+                let (rest, value) = be_i128.parse(input)?;
+                match Integer::from_i128(value) {
+                    Some(value) => Ok((rest, Plist::Integer(value))),
+                    None => fail(input, ErrorKind::BadInteger(value.to_string())),
+                }
+            }
+            _ => fail(input, ErrorKind::InvalidMarker(0x10 | extra_info)),
         }
     }
-    fn parse_ascii_string(input: &[u8], extra_info: u8) -> IResult<&[u8], Plist> {
-        let (input, len) = if extra_info == 0xF {
-            Self::parse_count(input)?
-        } else {
-            (input, extra_info as usize)
-        };
-        let mut raw_utf16: Vec<u16> = vec![];
-        let mut input = input;
-        for _ in 0..len {
-            let value: u16;
-            (input, value) = be_u16.parse(input)?;
-            raw_utf16.push(value);
+    //UID: 1~16 字节大端整数, 超过 8 字节时高位必须为 0
+    fn parse_uid(input: &[u8], extra_info: u8) -> BinaryResult<'_, Plist> {
+        let (rest, bytes) = take(extra_info as usize + 1).parse(input)?;
+        let (high, low) = bytes.split_at(bytes.len().saturating_sub(8));
+        if high.iter().any(|b| *b != 0) {
+            return fail(input, ErrorKind::BadInteger(format!("{:02X?}", bytes)));
         }
-        let str_value = String::from_utf16(&raw_utf16).map_err(|_| {
-            nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Fail))
-        })?;
-        Ok((input, Plist::String(str_value)))
+        Ok((rest, Plist::Uid(Self::be_uint(low))))
     }
-    fn parse_string(input: &[u8], extra_info: u8) -> IResult<&[u8], Plist> {
-        let (input, len) = if extra_info == 0xF {
-            Self::parse_count(input)?
-        } else {
-            (input, extra_info as usize)
+    fn parse_ascii_string(input: &[u8], extra_info: u8) -> BinaryResult<'_, Plist> {
+        let (input, len) = Self::parse_length(input, extra_info)?;
+        let Some(size) = len.checked_mul(2) else {
+            return fail(input, ErrorKind::UnexpectedEof);
         };
+        let (rest, bytes) = take(size).parse(input)?;
+        let raw_utf16: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect();
+        match String::from_utf16(&raw_utf16) {
+            Ok(str_value) => Ok((rest, Plist::String(str_value))),
+            Err(_) => fail(input, ErrorKind::InvalidUtf16),
+        }
+    }
+    fn parse_string(input: &[u8], extra_info: u8) -> BinaryResult<'_, Plist> {
+        let (input, len) = Self::parse_length(input, extra_info)?;
         let (input, str_bytes) = take(len).parse(input)?;
         let str_value = String::from_utf8_lossy(str_bytes).to_string();
         Ok((input, Plist::String(str_value)))
     }
-    fn parse_offset_table(input: &[u8], counts: u64, int_size: u8) -> IResult<&[u8], Vec<usize>> {
-        let counts = counts as usize;
-        match int_size {
-            1 => count(map(be_u8, |v| v as usize), counts).parse(input),
-            2 => count(map(be_u16, |v| v as usize), counts).parse(input),
-            4 => count(map(be_u32, |v| v as usize), counts).parse(input),
-            8 => count(map(be_u64, |v| v as usize), counts).parse(input),
-            _ => panic!("Invalid offset int size"),
+    //1~8 字节的大端无符号整数
+    fn be_uint(bytes: &[u8]) -> u64 {
+        bytes.iter().fold(0_u64, |acc, b| (acc << 8) | *b as u64)
+    }
+    //偏移表和对象引用的整数大小可以是 1~8 字节
+    fn parse_uints(input: &[u8], counts: usize, int_size: u8) -> BinaryResult<'_, Vec<u64>> {
+        let available = input.len() / int_size as usize;
+        if counts > available {
+            return fail(input, ErrorKind::UnexpectedEof);
         }
+        count(map(take(int_size), Self::be_uint), counts).parse(input)
     }
-    pub fn parse(input: &[u8]) -> IResult<&[u8], Plist> {
-        let (_, _) = Self::parse_bplist_header(input)?;
-        let (_, trailer) = Self::parse_trailer(&input[input.len() - 32..])?;
-        let offset_table_start = trailer.offset_table_start as usize;
-        let (_, offsets) = Self::parse_offset_table(
-            &input[offset_table_start..],
-            trailer.num_objects,
+    pub(crate) fn parse_with(input: &'a [u8], options: &ParseOptions) -> Result<Plist, Error> {
+        Self::parse_bplist_header(input).map_err(|e| Self::error(input, e))?;
+        let Some(trailer_start) = input.len().checked_sub(32).filter(|start| *start >= 8) else {
+            return Err(Error::Binary {
+                kind: ErrorKind::UnexpectedEof,
+                offset: input.len(),
+            });
+        };
+        let (_, trailer) =
+            Self::parse_trailer(&input[trailer_start..]).map_err(|e| Self::error(input, e))?;
+        let trailer_error = |field: usize, reason| Error::Binary {
+            kind: ErrorKind::InvalidTrailer(reason),
+            offset: trailer_start + field,
+        };
+        if !(1..=8).contains(&trailer.offset_table_offset_size) {
+            return Err(trailer_error(6, "invalid offset size"));
+        }
+        if !(1..=8).contains(&trailer.object_ref_size) {
+            return Err(trailer_error(7, "invalid object ref size"));
+        }
+        let num_objects = usize::try_from(trailer.num_objects)
+            .map_err(|_| trailer_error(8, "too many objects"))?;
+        if trailer.top_object_offset >= trailer.num_objects {
+            return Err(trailer_error(16, "top object out of range"));
+        }
+        //偏移表必须位于头部和尾部之间
+        let offset_table_start = usize::try_from(trailer.offset_table_start)
+            .ok()
+            .filter(|start| (8..=trailer_start).contains(start))
+            .ok_or_else(|| trailer_error(24, "offset table out of range"))?;
+        let (_, offsets) = Self::parse_uints(
+            &input[offset_table_start..trailer_start],
+            num_objects,
             trailer.offset_table_offset_size,
-        )?;
-        let offset = offsets[trailer.top_object_offset as usize];
-        Self::parse_object(input, offset, &offsets, &trailer)
+        )
+        .map_err(|e| Self::error(input, e))?;
+        //对象只能位于头部之后、偏移表之前
+        if let Some(index) = offsets
+            .iter()
+            .position(|offset| !(8..trailer.offset_table_start).contains(offset))
+        {
+            return Err(Error::Binary {
+                kind: ErrorKind::OffsetOutOfRange(offsets[index]),
+                offset: offset_table_start + index * trailer.offset_table_offset_size as usize,
+            });
+        }
+        let mut reader = BinaryReader {
+            data: input,
+            trailer,
+            in_path: vec![false; offsets.len()],
+            offsets,
+            options: options.clone(),
+            max_objects: options.object_limit(input),
+            depth: 0,
+            objects: 0,
+        };
+        reader.parse_object(reader.trailer.top_object_offset)
+    }
+    fn error(data: &[u8], err: nom::Err<BinaryError>) -> Error {
+        match err {
+            //出错位置是 data 的子切片, 用指针差计算偏移
+            nom::Err::Error(e) | nom::Err::Failure(e) => Error::Binary {
+                kind: e.kind,
+                offset: e.input.as_ptr() as usize - data.as_ptr() as usize,
+            },
+            nom::Err::Incomplete(_) => Error::Binary {
+                kind: ErrorKind::UnexpectedEof,
+                offset: data.len(),
+            },
+        }
     }
-    fn parse_float(input: &[u8], extra_info: u8) -> IResult<&[u8], Plist> {
+    fn parse_float(input: &[u8], extra_info: u8) -> BinaryResult<'_, Plist> {
         match extra_info {
-            0 => map(be_f32, |v| Plist::Float(v as f64)).parse(input),
-            2 => map(be_f32, |v| Plist::Float(v as f64)).parse(input),
-            3 => map(be_f64, |v| Plist::Float(v as f64)).parse(input),
-            _ => Err(nom::Err::Error(nom::error::Error::new(
-                input,
-                nom::error::ErrorKind::Switch,
-            ))),
+            0 => map(be_f32, Plist::from).parse(input),
+            2 => map(be_f32, Plist::from).parse(input),
+            3 => map(be_f64, Plist::from).parse(input),
+            _ => fail(input, ErrorKind::InvalidMarker(0x20 | extra_info)),
         }
     }
-    fn parse_bool(input: &[u8], extra_info: u8) -> IResult<&[u8], Plist> {
+    fn parse_bool(input: &[u8], extra_info: u8) -> BinaryResult<'_, Plist> {
         Ok((
             input,
             match extra_info {
                 0x00 => Plist::Boolean(false),
                 0x08 => Plist::Boolean(false),
                 0x09 => Plist::Boolean(true),
-                _ => {
-                    return Err(nom::Err::Failure(nom::error::Error::new(
-                        input,
-                        nom::error::ErrorKind::TooLarge,
-                    )));
-                }
+                _ => return fail(input, ErrorKind::InvalidMarker(extra_info)),
             },
         ))
     }
-    fn parse_date(input: &[u8], _extra_info: u8) -> IResult<&[u8], Plist> {
-        let (input, timestamp) = recognize(be_f64).parse(input)?;
-        let bytes: [u8; 8] = timestamp.try_into().unwrap();
-        let seconds_since_2001 = f64::from_be_bytes(bytes);
-        let unix_timestamp = seconds_since_2001 + 978307200.0;
-
-        // 5. 转换为 DateTime<Utc>
-        let naive =
-            DateTime::from_timestamp(unix_timestamp as i64, (unix_timestamp.fract() * 1e9) as u32)
-                .unwrap();
-        let datetime = DateTime::<Utc>::from(naive);
-        Ok((input, Plist::Date(datetime)))
+    //直接保存 2001 年以来的秒数, NaN 和超出 chrono 范围的时间也不会丢失
+    fn parse_date(input: &[u8], _extra_info: u8) -> BinaryResult<'_, Plist> {
+        map(be_f64, |seconds| {
+            Plist::Date(Date::from_seconds_since_2001(seconds))
+        })
+        .parse(input)
     }
-    fn parse_count(input: &[u8]) -> IResult<&[u8], usize> {
-        let (input, header) = be_u8.parse(input)?;
-        let byte_count = 1 << (header & 0x0F);
-        match byte_count {
-            1 => map(be_u8, |v| v as usize).parse(input),
-            2 => map(be_u16, |v| v as usize).parse(input),
-            4 => map(be_u32, |v| v as usize).parse(input),
-            8 => map(be_u64, |v| v as usize).parse(input),
-            _ => Err(nom::Err::Failure(nom::error::Error::new(
-                input,
-                nom::error::ErrorKind::TooLarge,
-            ))),
+    //长度整数对象: 0x1N 后跟 2^N 字节
+    fn parse_count(input: &[u8]) -> BinaryResult<'_, usize> {
+        let (rest, header) = be_u8.parse(input)?;
+        let byte_count = 1_usize << (header & 0x0F);
+        if header >> 4 != 0x1 || byte_count > 8 {
+            return fail(input, ErrorKind::InvalidMarker(header));
+        }
+        let (rest, bytes) = take(byte_count).parse(rest)?;
+        match usize::try_from(Self::be_uint(bytes)) {
+            Ok(len) => Ok((rest, len)),
+            Err(_) => fail(input, ErrorKind::UnexpectedEof),
         }
     }
-    fn parse_data(input: &[u8], extra_info: u8) -> IResult<&[u8], Plist> {
-        let (input, len) = if extra_info == 0xF {
-            Self::parse_count(input)?
+    //低 4 位为 0xF 时长度写在后面的整数对象中
+    fn parse_length(input: &[u8], extra_info: u8) -> BinaryResult<'_, usize> {
+        if extra_info == 0xF {
+            Self::parse_count(input)
         } else {
-            (input, extra_info as usize)
-        };
+            Ok((input, extra_info as usize))
+        }
+    }
+    fn parse_data(input: &[u8], extra_info: u8) -> BinaryResult<'_, Plist> {
+        let (input, len) = Self::parse_length(input, extra_info)?;
         let (input, data) = take(len).parse(input)?;
         Ok((input, Plist::Data(data.to_vec())))
     }
-    fn parse_array<'a>(
-        data: &'a [u8],
-        offset: usize,
-        extra_info: u8,
-        trailer: &Trailer,
-        offsets: &[usize],
-    ) -> IResult<&'a [u8], Plist> {
-        let input = &data[offset..];
-        let (input, counts) = if extra_info == 0xF {
-            Self::parse_count(input)?
-        } else {
-            (input, extra_info as usize)
-        };
-        let (input, refs) = match trailer.object_ref_size {
-            1 => count(map(be_u8, |v| v as usize), counts).parse(input)?,
-            2 => count(map(be_u16, |v| v as usize), counts).parse(input)?,
-            4 => count(map(be_u32, |v| v as usize), counts).parse(input)?,
-            8 => count(map(be_u64, |v| v as usize), counts).parse(input)?,
-            _ => panic!("Invalid object ref size"),
-        };
-        let mut array = Vec::with_capacity(counts);
-        for object_ref_offset in refs {
-            let (_, obj) = Self::parse_object(data, offsets[object_ref_offset], offsets, trailer)?;
-            array.push(obj);
+    fn parse_refs(&self, input: &'a [u8], counts: usize) -> Result<Vec<u64>, Error> {
+        let ref_size = self.trailer.object_ref_size as usize;
+        let (_, refs) = Self::parse_uints(input, counts, ref_size as u8)
+            .map_err(|e| Self::error(self.data, e))?;
+        //引用必须指向偏移表中的对象
+        if let Some(index) = refs
+            .iter()
+            .position(|object_ref| *object_ref >= self.trailer.num_objects)
+        {
+            let kind = ErrorKind::ObjectRefOutOfRange(refs[index]);
+            let err = fail::<()>(&input[index * ref_size..], kind).unwrap_err();
+            return Err(Self::error(self.data, err));
         }
-        Ok((input, Plist::Array(array)))
+        Ok(refs)
     }
-    fn parse_dict<'a>(
-        data: &'a [u8],
-        offset: usize,
-        extra_info: u8,
-        trailer: &Trailer,
-        offsets: &[usize],
-    ) -> IResult<&'a [u8], Plist> {
-        let input = &data[offset..];
-        let (input, counts) = if extra_info == 0xF {
-            Self::parse_count(input)?
-        } else {
-            (input, extra_info as usize)
-        };
-        //先解析所有key refs
-        let (input, key_refs) = match trailer.object_ref_size {
-            1 => count(map(be_u8, |v| v as usize), counts).parse(input)?,
-            2 => count(map(be_u16, |v| v as usize), counts).parse(input)?,
-            4 => count(map(be_u32, |v| v as usize), counts).parse(input)?,
-            8 => count(map(be_u64, |v| v as usize), counts).parse(input)?,
-            _ => panic!("Invalid object ref size"),
-        };
-        let (input, value_refs) = match trailer.object_ref_size {
-            1 => count(map(be_u8, |v| v as usize), counts).parse(input)?,
-            2 => count(map(be_u16, |v| v as usize), counts).parse(input)?,
-            4 => count(map(be_u32, |v| v as usize), counts).parse(input)?,
-            8 => count(map(be_u64, |v| v as usize), counts).parse(input)?,
-            _ => panic!("Invalid object ref size"),
-        };
+    //数组和集合的格式相同
+    fn parse_array(&mut self, input: &'a [u8], extra_info: u8) -> Result<Vec<Plist>, Error> {
+        let (input, counts) =
+            Self::parse_length(input, extra_info).map_err(|e| Self::error(self.data, e))?;
+        let refs = self.parse_refs(input, counts)?;
+        let mut array = Vec::with_capacity(refs.len());
+        for object_ref in refs {
+            array.push(self.parse_object(object_ref)?);
+        }
+        Ok(array)
+    }
+    fn parse_dict(&mut self, input: &'a [u8], extra_info: u8) -> Result<Plist, Error> {
+        let (input, counts) =
+            Self::parse_length(input, extra_info).map_err(|e| Self::error(self.data, e))?;
+        //先解析所有key refs, 再解析value refs
+        let refs = self.parse_refs(input, counts.saturating_mul(2))?;
+        let (key_refs, value_refs) = refs.split_at(counts);
         let mut dict = IndexMap::new();
-        let mut keys = vec![];
-        for index in key_refs {
-            let (_, key) = Self::parse_object(data, offsets[index], offsets, trailer)?;
-            if let Plist::String(key) = key {
-                keys.push(key);
+        for (key_index, value_index) in key_refs.iter().zip(value_refs) {
+            let offset = self.offsets[*key_index as usize] as usize;
+            let key = match self.parse_object(*key_index)? {
+                Plist::String(key) => key,
+                _ if self.options.strict => {
+                    return Err(Error::Binary {
+                        kind: ErrorKind::InvalidKey,
+                        offset,
+                    });
+                }
+                _ => continue,
+            };
+            let value = self.parse_object(*value_index)?;
+            match (dict.contains_key(&key), self.options.duplicate_keys) {
+                (true, DuplicateKeys::Error) => {
+                    return Err(Error::Binary {
+                        kind: ErrorKind::DuplicateKey(key),
+                        offset,
+                    });
+                }
+                (true, DuplicateKeys::KeepFirst) => {}
+                _ => {
+                    dict.insert(key, value);
+                }
             }
         }
-        for (key_string, value_index) in keys.into_iter().zip(value_refs) {
-            let new_offset = offsets[value_index];
-            let (_, key) = Self::parse_object(data, new_offset, offsets, trailer)?;
-            dict.insert(key_string, key);
+        Ok(Plist::Dictionary(dict))
+    }
+    //数组、集合和字典需要检查循环引用和嵌套深度
+    fn parse_container(
+        &mut self,
+        index: u64,
+        object_type: u8,
+        input: &'a [u8],
+        extra_info: u8,
+    ) -> Result<Plist, Error> {
+        let offset = self.offsets[index as usize] as usize;
+        if self.in_path[index as usize] {
+            return Err(Error::Binary {
+                kind: ErrorKind::RecursiveObject(index),
+                offset,
+            });
         }
-        Ok((input, Plist::Dictionary(dict)))
+        if self.depth >= self.options.max_depth {
+            return Err(Error::Binary {
+                kind: ErrorKind::DepthLimitExceeded(self.options.max_depth),
+                offset,
+            });
+        }
+        self.in_path[index as usize] = true;
+        self.depth += 1;
+        let result = match object_type {
+            0xA => self.parse_array(input, extra_info).map(Plist::Array),
+            0xC => self.parse_array(input, extra_info).map(Plist::Set),
+            _ => self.parse_dict(input, extra_info),
+        };
+        self.depth -= 1;
+        self.in_path[index as usize] = false;
+        result
     }
-    fn parse_object<'a>(
-        data: &'a [u8],
-        offset: usize,
-        offsets: &[usize],
-        trailer: &Trailer,
-    ) -> IResult<&'a [u8], Plist> {
-        let input = &data[offset..];
-        let (input, (object_type, extra_info)) = Self::parse_header(input)?;
-        match object_type {
-            0x0 => Self::parse_bool(input, extra_info),
-            0x1 => Self::parse_integer(input, extra_info),
-            0x2 => Self::parse_float(input, extra_info),
-            0x3 => Self::parse_date(input, extra_info),
-            0x4 => Self::parse_data(input, extra_info),
-            0x5 => Self::parse_string(input, extra_info),
-            0x6 => Self::parse_ascii_string(input, extra_info),
-            0xA => Self::parse_array(data, offset + 1, extra_info, trailer, offsets),
-            0xD => Self::parse_dict(data, offset + 1, extra_info, trailer, offsets),
-            _ => Err(nom::Err::Error(nom::error::Error::new(
-                input,
-                nom::error::ErrorKind::Switch,
-            ))),
+    fn parse_object(&mut self, index: u64) -> Result<Plist, Error> {
+        let offset = self.offsets[index as usize] as usize;
+        self.objects += 1;
+        if self.objects > self.max_objects {
+            return Err(Error::Binary {
+                kind: ErrorKind::ObjectLimitExceeded(self.max_objects),
+                offset,
+            });
         }
+        let input = &self.data[offset..self.trailer.offset_table_start as usize];
+        let (input, (object_type, extra_info)) =
+            Self::parse_header(input).map_err(|e| Self::error(self.data, e))?;
+        //先校验对象头, 错误定位到对象起始位置
+        let result = match (object_type, extra_info) {
+            (0x0, 0x0 | 0x8 | 0x9) => Self::parse_bool(input, extra_info),
+            (0x1, 0x0..=0x4) => Self::parse_integer(input, extra_info),
+            (0x2, 0x0 | 0x2 | 0x3) => Self::parse_float(input, extra_info),
+            (0x3, 0x3) => Self::parse_date(input, extra_info),
+            (0x4, _) => Self::parse_data(input, extra_info),
+            (0x5, _) => Self::parse_string(input, extra_info),
+            (0x6, _) => Self::parse_ascii_string(input, extra_info),
+            (0x8, _) => Self::parse_uid(input, extra_info),
+            (0xA | 0xC | 0xD, _) => {
+                return self.parse_container(index, object_type, input, extra_info);
+            }
+            _ => {
+                return Err(Error::Binary {
+                    kind: ErrorKind::InvalidMarker(object_type << 4 | extra_info),
+                    offset,
+                });
+            }
+        };
+        result
+            .map(|(_, value)| value)
+            .map_err(|e| Self::error(self.data, e))
     }
 }
@@ -1,158 +1,256 @@
+use crate::date::Date;
 use crate::error::Error;
+use crate::integer::Integer;
+use crate::options::{BinaryWriteOptions, Deduplication};
 use crate::plist::Plist;
-use chrono::{DateTime, Utc};
-use std::io::{Cursor, Write};
+use crate::real::Real;
+use std::collections::HashMap;
+use std::io::Write;
 
-pub(crate) struct BinaryWriter {
-    objects: u64,
+//展开后的对象: 标量直接保存序列化结果, 容器保存子对象索引, 等对象总数确定后再序列化
+enum Object {
+    Scalar(Vec<u8>),
+    //数组和集合, 第一个字段是对象类型 0xA 或 0xC
+    Array(u8, Vec<u64>),
+    Dictionary(Vec<u64>, Vec<u64>),
+}
+//去重用的键, 容器按子对象索引比较
+#[derive(PartialEq, Eq, Hash)]
+enum ObjectKey<'a> {
+    Boolean(bool),
+    Integer(Integer),
+    Float(u64, bool),
+    String(&'a str),
+    Date(u64),
+    Data(&'a [u8]),
+    Uid(u64),
+    Array(u8, Vec<u64>),
+    Dictionary(Vec<u64>, Vec<u64>),
+}
+pub(crate) struct BinaryWriter<'a> {
+    deduplication: Deduplication,
+    apple_style: bool,
+    objects: Vec<Object>,
+    unique: HashMap<ObjectKey<'a>, u64>,
     offsets: Vec<u64>, // 每个对象的偏移量
     ref_size: u8,      // 对象引用大小 (1/2/4/8字节)
     offset_size: u8,   // 偏移表条目大小 (1/2/4/8字节)
 }
-impl BinaryWriter {
-    pub fn new() -> Self {
+impl<'a> BinaryWriter<'a> {
+    pub fn new(options: &BinaryWriteOptions) -> Self {
         BinaryWriter {
-            objects: 0,
+            deduplication: options.deduplication,
+            apple_style: options.apple_style,
+            objects: vec![],
+            unique: HashMap::new(),
             offsets: vec![],
             ref_size: 1,
             offset_size: 1,
         }
     }
 
-    pub fn write<W: Write>(mut self, value: &Plist, output: &mut W) -> Result<(), Error> {
-        // 1. 收集所有对象并生成二进制数据
-        let mut bytes = vec![];
-        let (objects_data, _) = self.collect_objects(value, &mut bytes)?;
-        //2. 写入头部
+    pub fn write<W: Write>(mut self, value: &'a Plist, output: &mut W) -> Result<(), Error> {
+        // 1. 展开所有对象, 得到对象总数
+        let root = self.flatten(value);
+        self.unique.clear();
+        // 2. 根据对象总数计算引用大小
+        self.ref_size = self.convert_length(self.objects.len() as u64);
+        // 3. 写入头部和对象, 记录偏移
         output.write_all(b"bplist00")?;
-        //3. 写入偏移表
-        let mut cursor = Cursor::new(vec![]);
-        for (_, data) in objects_data.iter().enumerate() {
-            self.offsets.push(cursor.position() + 8);
-            cursor.write_all(data)?;
+        let mut position = 8_u64;
+        let objects = std::mem::take(&mut self.objects);
+        for object in &objects {
+            self.offsets.push(position);
+            let bytes = match object {
+                Object::Scalar(bytes) => bytes,
+                Object::Array(code, refs) => &self.serialize_container(*code, refs.len(), refs),
+                Object::Dictionary(keys, values) => {
+                    &self.serialize_container(0xD, keys.len(), keys.iter().chain(values))
+                }
+            };
+            output.write_all(bytes)?;
+            position += bytes.len() as u64;
         }
-        let object_bytes = cursor.into_inner();
-        let offset_table_start = object_bytes.len() + 8;
-        output.write_all(&object_bytes)?;
-        //4. 计算元数据
-        self.calculate_sizes();
-        //5. 写入偏移表
+        // 4. 偏移表起始位置是最大的偏移, 据此计算偏移大小
+        let offset_table_start = position;
+        self.offset_size = self.convert_length(offset_table_start);
+        // 5. 写入偏移表
         let offset_table = self.generate_offset_table()?;
         output.write_all(&offset_table)?;
         // 6. 写入尾部
-        let trailer_table = self.generate_trailer(0, bytes.len() as u64, offset_table_start as u64)?;
+        let trailer_table =
+            self.generate_trailer(root, objects.len() as u64, offset_table_start)?;
         output.write_all(&trailer_table)?;
         Ok(())
     }
 
-    fn collect_objects<'a>(
-        &mut self,
-        value: &Plist,
-        mem_bytes: &'a mut Vec<(u64, Vec<Vec<u8>>)>,
-    ) -> Result<(Vec<Vec<u8>>, Vec<u8>), Error> {
-        let index = self.objects;
-        self.objects += 1;
-        let bytes = self.serialize_object(value, mem_bytes)?;
-        let exit_bytes = mem_bytes.iter().find(|(_, d)| **d == bytes);
-        let (bytes, index) = if let Some((key_idx, _)) = exit_bytes {
-            self.objects -= 1;
-            (vec![], *key_idx)
+    //深度优先展开对象, 返回对象索引
+    fn flatten(&mut self, value: &'a Plist) -> u64 {
+        match value {
+            Plist::Array(list) => self.flatten_list(0xA, list),
+            Plist::Set(list) => self.flatten_list(0xC, list),
+            Plist::Dictionary(dict) => {
+                let index = self.objects.len();
+                self.objects.push(Object::Dictionary(vec![], vec![]));
+                let keys: Vec<u64> = dict.keys().map(|key| self.flatten_key(key)).collect();
+                let values: Vec<u64> = dict.values().map(|value| self.flatten(value)).collect();
+                let key = || ObjectKey::Dictionary(keys.clone(), values.clone());
+                if let Some(exist) = self.find_container(key, index) {
+                    return exist;
+                }
+                self.objects[index] = Object::Dictionary(keys, values);
+                index as u64
+            }
+            //按 CoreFoundation 源码的写法: 布尔值不去重但是单例, 重复的对象照样写出, 引用都指向第一个
+            Plist::Boolean(v) if self.apple_style => {
+                let index = self.push_scalar(value);
+                *self.unique.entry(ObjectKey::Boolean(*v)).or_insert(index)
+            }
+            //UID 也不去重
+            Plist::Uid(_) if self.apple_style => self.push_scalar(value),
+            Plist::Boolean(v) => self.insert_scalar(ObjectKey::Boolean(*v), value),
+            Plist::Integer(v) => self.insert_scalar(ObjectKey::Integer(*v), value),
+            Plist::Float(v) => {
+                let key = ObjectKey::Float(v.value().to_bits(), v.is_single());
+                self.insert_scalar(key, value)
+            }
+            Plist::String(v) => self.flatten_key(v),
+            Plist::Date(v) => {
+                let key = ObjectKey::Date(v.seconds_since_2001().to_bits());
+                self.insert_scalar(key, value)
+            }
+            Plist::Data(v) => self.insert_scalar(ObjectKey::Data(v), value),
+            Plist::Uid(v) => self.insert_scalar(ObjectKey::Uid(*v), value),
+        }
+    }
+    fn flatten_list(&mut self, code: u8, list: &'a [Plist]) -> u64 {
+        let index = self.objects.len();
+        self.objects.push(Object::Array(code, vec![]));
+        let refs: Vec<u64> = list.iter().map(|item| self.flatten(item)).collect();
+        if let Some(exist) = self.find_container(|| ObjectKey::Array(code, refs.clone()), index) {
+            return exist;
+        }
+        self.objects[index] = Object::Array(code, refs);
+        index as u64
+    }
+    fn flatten_key(&mut self, key: &'a str) -> u64 {
+        let dedup = self.apple_style || self.deduplication != Deduplication::None;
+        if dedup && let Some(index) = self.unique.get(&ObjectKey::String(key)) {
+            return *index;
+        }
+        let index = self.objects.len() as u64;
+        self.objects
+            .push(Object::Scalar(self.serialize_string(key)));
+        if dedup {
+            self.unique.insert(ObjectKey::String(key), index);
+        }
+        index
+    }
+    fn insert_scalar(&mut self, key: ObjectKey<'a>, value: &Plist) -> u64 {
+        let dedup = self.apple_style || self.deduplication != Deduplication::None;
+        if dedup && let Some(index) = self.unique.get(&key) {
+            return *index;
+        }
+        let index = self.push_scalar(value);
+        if dedup {
+            self.unique.insert(key, index);
+        }
+        index
+    }
+    fn push_scalar(&mut self, value: &Plist) -> u64 {
+        let index = self.objects.len() as u64;
+        self.objects
+            .push(Object::Scalar(self.serialize_scalar(value)));
+        index
+    }
+    //容器展开前已经占了 index 位置; 内容相同的容器的子对象必然都已去重, 所以占位对象一定在末尾
+    //key 要复制全部引用, 只有需要对容器去重时才生成
+    fn find_container(&mut self, key: impl FnOnce() -> ObjectKey<'a>, index: usize) -> Option<u64> {
+        //apple_style 不对数组和字典去重
+        if self.apple_style || self.deduplication != Deduplication::All {
+            return None;
+        }
+        let key = key();
+        if let Some(exist) = self.unique.get(&key) {
+            debug_assert_eq!(index, self.objects.len() - 1);
+            self.objects.truncate(index);
+            return Some(*exist);
+        }
+        self.unique.insert(key, index as u64);
+        None
+    }
+    fn serialize_container<'r>(
+        &self,
+        code: u8,
+        len: usize,
+        refs: impl IntoIterator<Item = &'r u64>,
+    ) -> Vec<u8> {
+        let (marker, len_bytes) = self.serialize_length(code, len);
+        let mut buffer = vec![marker];
+        buffer.extend(len_bytes);
+        for index in refs {
+            buffer.extend(self.serialize_ref(*index));
+        }
+        buffer
+    }
+    fn serialize_string(&self, value: &str) -> Vec<u8> {
+        let mut buffer = vec![];
+        if value.is_ascii() {
+            let bytes = value.as_bytes();
+            let (marker, len_bytes) = self.serialize_length(0x5, bytes.len());
+            buffer.push(marker);
+            buffer.extend(len_bytes);
+            buffer.extend(bytes);
         } else {
-            mem_bytes.push((index, bytes.clone()));
-            (bytes, index)
-        };
-        Ok((bytes, self.serialize_ref(index)))
+            let utf16_len = value.encode_utf16().count();
+            let (marker, len_bytes) = self.serialize_length(0x6, utf16_len);
+            buffer.push(marker);
+            buffer.extend(len_bytes);
+            for c in value.encode_utf16() {
+                buffer.extend(&c.to_be_bytes());
+            }
+        }
+        buffer
     }
-    fn serialize_object<'a>(
-        &mut self,
-        value: &Plist,
-        mem_bytes: &'a mut Vec<(u64, Vec<Vec<u8>>)>,
-    ) -> Result<Vec<Vec<u8>>, Error> {
+    fn serialize_scalar(&self, value: &Plist) -> Vec<u8> {
         let mut buffer = vec![];
-        let mut list = vec![];
         match value {
-            Plist::Array(value) => {
-                let (marker, len_bytes) = self.serialize_length(0xA, value.len());
-                buffer.push(marker);
-                buffer.extend(len_bytes);
-                let mut datas = vec![];
-                for elem in value {
-                    let (data, ref_bytes) = self.collect_objects(elem, mem_bytes)?;
-                    buffer.extend(ref_bytes);
-                    datas.extend(data);
-                }
-                list.push(buffer);
-                list.extend(datas);
-            }
-            Plist::Dictionary(dict) => {
-                let (marker, len_bytes) = self.serialize_length(0xD, dict.len());
-                buffer.push(marker);
-                buffer.extend(len_bytes);
-                let mut datas = vec![];
-                for (key, _) in dict {
-                    let key_plist = Plist::String(key.clone());
-                    let (data, ref_bytes) = self.collect_objects(&key_plist, mem_bytes)?;
-                    buffer.extend(ref_bytes);
-                    datas.extend(data);
-                }
-                for (_, value) in dict {
-                    let (data, ref_bytes) = self.collect_objects(value, mem_bytes)?;
-                    buffer.extend(ref_bytes);
-                    datas.extend(data);
-                }
-                list.push(buffer);
-                list.extend(datas);
+            Plist::Array(_) | Plist::Set(_) | Plist::Dictionary(_) => {
+                unreachable!("containers are flattened")
             }
             Plist::Boolean(value) => {
                 let marker = if *value { 0x09 } else { 0x08 };
                 buffer.push(marker);
-                list.push(buffer);
             }
             Plist::Integer(value) => {
                 let (marker, bytes) = self.serialize_integer(0x1, *value);
                 buffer.push(marker);
                 buffer.extend(bytes);
-                list.push(buffer);
             }
             Plist::Float(value) => {
-                let (marker, bytes) = self.serialize_float(0x2, *value);
+                let (marker, bytes) = self.serialize_float(0x2, value);
                 buffer.push(marker);
                 buffer.extend(bytes);
-                list.push(buffer);
             }
-            Plist::String(value) if value.is_ascii() => {
-                let bytes = value.as_bytes();
-                let (marker, len_bytes) = self.serialize_length(0x5, bytes.len());
+            Plist::String(value) => return self.serialize_string(value),
+            Plist::Uid(value) => {
+                let (marker, bytes) = self.serialize_uid(0x8, *value);
                 buffer.push(marker);
-                buffer.extend(len_bytes);
                 buffer.extend(bytes);
-                list.push(buffer);
-            }
-            Plist::String(value) => {
-                let utf16_len = value.encode_utf16().count();
-                let (marker, len_bytes) = self.serialize_length(0x6, utf16_len);
-                buffer.push(marker);
-                buffer.extend(len_bytes);
-                for c in value.encode_utf16() {
-                    buffer.extend(&c.to_be_bytes());
-                }
-                list.push(buffer);
             }
             Plist::Date(value) => {
                 let (marker, bytes) = self.serialize_date(0x3, *value);
                 buffer.push(marker);
                 buffer.extend(bytes);
-                list.push(buffer);
             }
             Plist::Data(value) => {
                 let (marker, bytes) = self.serialize_data(0x4, value);
                 buffer.push(marker);
                 buffer.extend(bytes);
                 buffer.extend(value);
-                list.push(buffer);
             }
         }
-        Ok(list)
+        buffer
     }
     fn generate_trailer(
         &self,
@@ -206,7 +304,8 @@ impl BinaryWriter {
     }
     fn serialize_count(&self, count: usize) -> Vec<u8> {
         let bytes_needed: u8 = self.convert_length(count as u64);
-        let type_byte = bytes_needed << 4;
+        //整数对象头: 0x1 << 4 | log2(字节数)
+        let type_byte = 0x10 | bytes_needed.trailing_zeros() as u8;
         let mut bytes = match bytes_needed {
             1 => vec![count as u8],
             2 => (count as u16).to_be_bytes().to_vec(),
@@ -217,47 +316,41 @@ impl BinaryWriter {
         bytes.insert(0, type_byte);
         bytes
     }
-    fn serialize_data(&self, code: u8, value: &Vec<u8>) -> (u8, Vec<u8>) {
-        let len = value.len();
-        if len < 0xF {
-            (code | len as u8, vec![])
-        } else {
-            let len_bytes = self.serialize_count(len);
-            (code | 0x0F, len_bytes)
-        }
+    fn serialize_data(&self, code: u8, value: &[u8]) -> (u8, Vec<u8>) {
+        self.serialize_length(code, value.len())
     }
-    fn serialize_date(&self, code: u8, value: DateTime<Utc>) -> (u8, Vec<u8>) {
-        let unix_timestamp = value.timestamp() as f64 + value.timestamp_subsec_nanos() as f64 / 1e9;
-        let seconds_since_2001 = unix_timestamp - 978_307_200.0;
+    fn serialize_date(&self, code: u8, value: Date) -> (u8, Vec<u8>) {
+        let seconds_since_2001 = value.seconds_since_2001();
         (code << 4 | 3, seconds_since_2001.to_be_bytes().to_vec())
     }
-    fn serialize_float(&self, code: u8, value: f64) -> (u8, Vec<u8>) {
-        let as_f32 = value as f32;
-        let is_lossless = (as_f32 as f64) == value;
-        let (extra_info, bytes) = if is_lossless && (value == 0.0 || value.abs() <= f32::MAX as f64)
-        {
-            // 使用 32-bit 浮点数（无精度丢失）
-            (0x0, as_f32.to_be_bytes().to_vec())
+    //按读取时的宽度写回; XML 中的实数和新建的 f64 都写成 8 字节, 与 CoreFoundation 相同
+    fn serialize_float(&self, code: u8, value: &Real) -> (u8, Vec<u8>) {
+        if value.is_single() {
+            let bytes = (value.value() as f32).to_be_bytes().to_vec();
+            ((code << 4) | 0x2, bytes)
         } else {
-            // 必须使用 64-bit 浮点数
-            (0x3, value.to_be_bytes().to_vec())
-        };
-        ((code << 4) | (extra_info & 0x0F), bytes)
+            ((code << 4) | 0x3, value.value().to_be_bytes().to_vec())
+        }
     }
-    fn serialize_integer(&self, code: u8, value: i64) -> (u8, Vec<u8>) {
+    fn serialize_integer(&self, code: u8, value: Integer) -> (u8, Vec<u8>) {
         let code = code << 4;
-        let (extra_info, bytes) = if value >= 0 {
-            match value {
-                0..=0xFF => (0x0, vec![value as u8]),
-                0x100..=0xFFFF => (0x1, (value as u16).to_be_bytes().to_vec()),
-                0x10000..=0xFFFFFFFF => (0x2, (value as u32).to_be_bytes().to_vec()),
-                _ => (0x3, value.to_be_bytes().to_vec()),
-            }
-        } else {
-            panic!("Negative integers not implemented");
+        //负数与 CoreFoundation 一致, 固定写成 8 字节补码; 超出 i64 的无符号数写成 16 字节
+        let value = value.to_i128();
+        let (extra_info, bytes) = match value {
+            0..=0xFF => (0x0, vec![value as u8]),
+            0x100..=0xFFFF => (0x1, (value as u16).to_be_bytes().to_vec()),
+            0x10000..=0xFFFFFFFF => (0x2, (value as u32).to_be_bytes().to_vec()),
+            0x100000000..=0x7FFFFFFFFFFFFFFF => (0x3, (value as i64).to_be_bytes().to_vec()),
+            _ if value < 0 => (0x3, (value as i64).to_be_bytes().to_vec()),
+            _ => (0x4, value.to_be_bytes().to_vec()),
         };
         (code | (extra_info & 0x0F), bytes)
     }
+    fn serialize_uid(&self, code: u8, value: u64) -> (u8, Vec<u8>) {
+        let size = self.convert_length(value);
+        let bytes = value.to_be_bytes()[8 - size as usize..].to_vec();
+        ((code << 4) | (size - 1), bytes)
+    }
     fn convert_length(&self, value: u64) -> u8 {
         match value {
             0..=0xFF => 1,
@@ -266,10 +359,4 @@ impl BinaryWriter {
             _ => 8,
         }
     }
-    fn calculate_sizes(&mut self) {
-        let max_ref = self.objects;
-        self.ref_size = self.convert_length(max_ref);
-        let max_offset = *self.offsets.last().unwrap_or(&0);
-        self.offset_size = self.convert_length(max_offset);
-    }
 }
@@ -1,4 +1,6 @@
-pub(crate) mod binary_writer;
+pub(crate) mod base64;
 pub(crate) mod binary_reader;
+pub(crate) mod binary_writer;
+pub(crate) mod text;
 pub(crate) mod xml_reader;
-pub(crate) mod xml_writer;
\ No newline at end of file
+pub(crate) mod xml_writer;
@@ -0,0 +1,31 @@
+use std::fmt;
+
+//栈上的定长文本缓冲, 格式化单个数字时不在堆上分配; 最长的 f64 十进制写法约 330 个字符
+pub(crate) struct TextBuffer {
+    bytes: [u8; 400],
+    len: usize,
+}
+impl TextBuffer {
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+    }
+}
+impl Default for TextBuffer {
+    fn default() -> Self {
+        TextBuffer {
+            bytes: [0; 400],
+            len: 0,
+        }
+    }
+}
+impl fmt::Write for TextBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if end > self.bytes.len() {
+            return Err(fmt::Error);
+        }
+        self.bytes[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
@@ -1,127 +1,590 @@
-use crate::error::Error;
+use crate::date::Date;
+use crate::error::{Error, ErrorKind};
+use crate::integer::Integer;
+use crate::options::{DuplicateKeys, ParseOptions};
 use crate::plist::Plist;
-use chrono::{DateTime, Utc};
+use crate::real::Real;
+use crate::stream::base64;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
 use indexmap::IndexMap;
 use nom::IResult;
 use nom::Parser;
 use nom::branch::alt;
-use nom::bytes::complete::{is_not, tag, take_until};
-use nom::character::complete::{char, digit1, multispace0};
-use nom::combinator::{map, map_res, opt, recognize, value};
+use nom::bytes::complete::{is_not, tag, take_till, take_until, take_while1};
+use nom::character::complete::{char, multispace0, multispace1};
+use nom::combinator::{opt, recognize};
+use nom::error::ParseError;
 use nom::multi::many0;
-use nom::sequence::{delimited, pair, terminated};
+use nom::sequence::{delimited, preceded, terminated};
+use std::borrow::Cow;
 
-pub struct XmlReader {}
-impl XmlReader {
-    fn parse_key(input: &str) -> IResult<&str, &str> {
-        let (input, _) = multispace0(input)?;
-        delimited(tag("<key>"), take_until("<"), tag("</key>")).parse(input)
-    }
-    fn parse_string(input: &str) -> IResult<&str, String> {
-        let (input, _) = multispace0(input)?;
-        if input.starts_with("<string/>") {
-            return value("".to_string(), tag("<string/>")).parse(input);
-        }
-        delimited(tag("<string>"), take_until("<"), tag("</string>"))
-            .parse(input)
-            .map(|(next_input, result)| (next_input, result.to_string()))
-    }
-    fn parse_float(input: &str) -> IResult<&str, f64> {
-        delimited(tag("<real>"), take_until("<"), tag("</real>"))
-            .parse(input)
-            .map(|(next_input, result)| (next_input, result.parse().unwrap()))
-    }
-    fn parse_date(input: &str) -> IResult<&str, DateTime<Utc>> {
-        delimited(tag("<date>"), take_until("<"), tag("</date>"))
-            .parse(input)
-            .map(|(next_input, result)| {
-                (
-                    next_input,
-                    DateTime::parse_from_rfc3339(result).unwrap().into(),
-                )
-            })
+//nom 解析错误, 记录出错位置和错误类型
+#[derive(Debug)]
+struct XmlError<'a> {
+    input: &'a str,
+    kind: ErrorKind,
+}
+impl<'a> ParseError<&'a str> for XmlError<'a> {
+    fn from_error_kind(input: &'a str, kind: nom::error::ErrorKind) -> Self {
+        let kind = if input.is_empty() || kind == nom::error::ErrorKind::TakeUntil {
+            ErrorKind::UnexpectedEof
+        } else {
+            ErrorKind::InvalidSyntax
+        };
+        XmlError { input, kind }
     }
-    fn parse_data(input: &str) -> IResult<&str, Vec<u8>> {
-        let (input, _) = multispace0(input)?;
-        if input.starts_with("<data/>") {
-            let (input, _) = tag("<data/>")(input)?;
-            return Ok((input, vec![]));
-        }
-        delimited(tag("<data>"), take_until("<"), tag("</data>"))
-            .parse(input)
-            .map(|(next_input, result)| (next_input, result.trim().as_bytes().to_vec()))
-    }
-    fn parse_integer(input: &str) -> IResult<&str, i64> {
-        let (input, _) = multispace0(input)?;
-        let (input, result) = map_res(
-            delimited(
-                tag("<integer>"),
-                recognize(pair(opt(alt((char('-'), char('+')))), digit1)),
-                tag("</integer>"),
-            ),
-            |s: &str| s.parse(),
+    fn append(_: &'a str, _: nom::error::ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+type XmlResult<'a, T> = IResult<&'a str, T, XmlError<'a>>;
+
+//XML 词法单元, 注释、处理指令和 DOCTYPE 在词法阶段跳过
+#[derive(Debug)]
+enum Token<'a> {
+    //开始标签, empty 表示 <tag/> 形式
+    Start { name: &'a str, empty: bool },
+    End(&'a str),
+    //已解码实体的文本或 CDATA
+    Text(String),
+}
+
+//XML 文本的编码
+#[derive(Debug, Clone, Copy)]
+enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+pub struct XmlReader<'a> {
+    text: &'a str,
+    input: &'a str,
+    options: ParseOptions,
+    max_objects: usize,
+    depth: usize,
+    objects: usize,
+}
+impl<'a> XmlReader<'a> {
+    //解码 &amp; &lt; &gt; &quot; &apos; 以及 &#NN; &#xHH; 字符引用
+    fn unescape(text: &str) -> Result<String, ErrorKind> {
+        let mut output = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find('&') {
+            output.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = rest
+                .find(';')
+                .ok_or_else(|| ErrorKind::BadEntity(rest.to_string()))?;
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                _ => {
+                    let code = if let Some(hex) = entity
+                        .strip_prefix("#x")
+                        .or_else(|| entity.strip_prefix("#X"))
+                    {
+                        u32::from_str_radix(hex, 16).ok()
+                    } else if let Some(dec) = entity.strip_prefix('#') {
+                        dec.parse::<u32>().ok()
+                    } else {
+                        None
+                    };
+                    code.and_then(char::from_u32)
+                        .ok_or_else(|| ErrorKind::BadEntity(format!("&{};", entity)))?
+                }
+            };
+            output.push(c);
+            rest = &rest[end + 1..];
+        }
+        output.push_str(rest);
+        Ok(output)
+    }
+    //实体解码失败时定位到文本起始位置
+    fn parse_escaped<'i>(input: &'i str, text: &str) -> Result<String, nom::Err<XmlError<'i>>> {
+        Self::unescape(text).map_err(|kind| nom::Err::Failure(XmlError { input, kind }))
+    }
+    fn parse_name(input: &str) -> XmlResult<'_, &str> {
+        take_while1(|c: char| c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.'))(input)
+    }
+    //属性值同样需要合法的实体, 但 plist 不使用属性的内容
+    fn parse_attribute(input: &str) -> XmlResult<'_, ()> {
+        let (input, _) = (
+            multispace1,
+            Self::parse_name,
+            multispace0,
+            char('='),
+            multispace0,
         )
+            .parse(input)?;
+        let (rest, text) = alt((
+            delimited(char('"'), take_till(|c| c == '"' || c == '<'), char('"')),
+            delimited(char('\''), take_till(|c| c == '\'' || c == '<'), char('\'')),
+        ))
         .parse(input)?;
-        Ok((input, result))
+        Self::parse_escaped(input, text)?;
+        Ok((rest, ()))
     }
-    fn parse_boolean(input: &str) -> IResult<&str, bool> {
-        let (input, _) = multispace0(input)?;
-        alt((value(true, tag("<true/>")), value(false, tag("<false/>")))).parse(input)
+    fn parse_start_tag(input: &str) -> XmlResult<'_, Token<'_>> {
+        let (input, (_, name, _, _, empty, _)) = (
+            char('<'),
+            Self::parse_name,
+            many0(Self::parse_attribute),
+            multispace0,
+            opt(char('/')),
+            char('>'),
+        )
+            .parse(input)?;
+        let empty = empty.is_some();
+        Ok((input, Token::Start { name, empty }))
     }
-
-    fn parse_dict(input: &str) -> IResult<&str, IndexMap<String, Plist>> {
-        let (input, _) = multispace0(input)?;
-        if input.starts_with("<dict/>") {
-            return value(IndexMap::new(), tag("<dict/>")).parse(input);
+    fn parse_end_tag(input: &str) -> XmlResult<'_, Token<'_>> {
+        let (input, (_, name, _, _)) =
+            (tag("</"), Self::parse_name, multispace0, char('>')).parse(input)?;
+        Ok((input, Token::End(name)))
+    }
+    fn parse_comment(input: &str) -> XmlResult<'_, &str> {
+        recognize(delimited(tag("<!--"), take_until("-->"), tag("-->"))).parse(input)
+    }
+    fn parse_pi(input: &str) -> XmlResult<'_, &str> {
+        recognize(delimited(tag("<?"), take_until("?>"), tag("?>"))).parse(input)
+    }
+    //<!DOCTYPE plist PUBLIC ...>, 可能带有 [...] 内部子集
+    fn parse_doctype(input: &str) -> XmlResult<'_, &str> {
+        recognize((
+            tag("<!DOCTYPE"),
+            take_till(|c| c == '[' || c == '>'),
+            opt(delimited(char('['), take_until("]"), char(']'))),
+            take_until(">"),
+            char('>'),
+        ))
+        .parse(input)
+    }
+    fn parse_cdata(input: &str) -> XmlResult<'_, &str> {
+        preceded(tag("<![CDATA["), terminated(take_until("]]>"), tag("]]>"))).parse(input)
+    }
+    //按 XML 规范把 \r\n 和单独的 \r 换成 \n, 字符引用 &#13; 不受影响
+    fn normalize_newlines(text: &str) -> Cow<'_, str> {
+        if text.contains('\r') {
+            Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"))
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+    fn parse_chars(input: &str) -> XmlResult<'_, String> {
+        let (rest, text) = is_not("<")(input)?;
+        let text = Self::normalize_newlines(text);
+        Ok((rest, Self::parse_escaped(input, &text)?))
+    }
+    //读取一个词法单元, 跳过的内容返回 None
+    fn parse_token(input: &str) -> XmlResult<'_, Option<Token<'_>>> {
+        if input.starts_with("<!--") {
+            Self::parse_comment(input).map(|(rest, _)| (rest, None))
+        } else if input.starts_with("<?") {
+            Self::parse_pi(input).map(|(rest, _)| (rest, None))
+        } else if input.starts_with("<!DOCTYPE") {
+            Self::parse_doctype(input).map(|(rest, _)| (rest, None))
+        } else if input.starts_with("<![CDATA[") {
+            Self::parse_cdata(input).map(|(rest, text)| {
+                let text = Self::normalize_newlines(text).into_owned();
+                (rest, Some(Token::Text(text)))
+            })
+        } else if input.starts_with("</") {
+            Self::parse_end_tag(input).map(|(rest, token)| (rest, Some(token)))
+        } else if input.starts_with('<') {
+            Self::parse_start_tag(input).map(|(rest, token)| (rest, Some(token)))
+        } else {
+            Self::parse_chars(input).map(|(rest, text)| (rest, Some(Token::Text(text))))
+        }
+    }
+    fn offset(&self) -> usize {
+        self.text.len() - self.input.len()
+    }
+    fn error(&self, kind: ErrorKind, offset: usize) -> Error {
+        Error::xml(kind, self.text, offset)
+    }
+    //读取下一个词法单元, 输入结束时返回 None
+    fn next_token(&mut self) -> Result<Option<(usize, Token<'a>)>, Error> {
+        while !self.input.is_empty() {
+            let offset = self.offset();
+            match Self::parse_token(self.input) {
+                Ok((rest, token)) => {
+                    self.input = rest;
+                    if let Some(token) = token {
+                        return Ok(Some((offset, token)));
+                    }
+                }
+                Err(nom::Err::Error(e) | nom::Err::Failure(e)) => {
+                    return Err(self.error(e.kind, self.text.len() - e.input.len()));
+                }
+                Err(nom::Err::Incomplete(_)) => {
+                    return Err(self.error(ErrorKind::UnexpectedEof, self.text.len()));
+                }
+            }
+        }
+        Ok(None)
+    }
+    fn next(&mut self) -> Result<(usize, Token<'a>), Error> {
+        match self.next_token()? {
+            Some(token) => Ok(token),
+            None => Err(self.error(ErrorKind::UnexpectedEof, self.offset())),
+        }
+    }
+    //元素之间只允许空白
+    fn next_markup(&mut self) -> Result<(usize, Token<'a>), Error> {
+        loop {
+            match self.next()? {
+                (_, Token::Text(text)) if text.trim().is_empty() => {}
+                (offset, Token::Text(_)) => {
+                    return Err(self.error(ErrorKind::InvalidSyntax, offset));
+                }
+                token => return Ok(token),
+            }
+        }
+    }
+    //元素的文本内容: 文本和 CDATA 拼接起来, 返回内容起始位置
+    fn parse_content(&mut self, name: &str, empty: bool) -> Result<(usize, String), Error> {
+        let start = self.offset();
+        let mut content = String::new();
+        if empty {
+            return Ok((start, content));
+        }
+        loop {
+            match self.next()? {
+                (_, Token::Text(text)) => content.push_str(&text),
+                (_, Token::End(end)) if end == name => return Ok((start, content)),
+                (offset, _) => return Err(self.error(ErrorKind::InvalidSyntax, offset)),
+            }
         }
-        let (input, _) = tag("<dict>")(input)?;
-        let (input, values) = many0((Self::parse_key, Self::parse_value)).parse(input)?;
+    }
+    //支持 nan、inf、+infinity、-infinity 等写法
+    fn convert_float(text: &str, keep_text: bool) -> Result<Real, ErrorKind> {
+        let s = text.trim();
+        let value: f64 = s
+            .parse()
+            .map_err(|_| ErrorKind::BadReal(text.to_string()))?;
+        Ok(match keep_text {
+            true => Real::from(value).with_text(s),
+            false => Real::from(value),
+        })
+    }
+    //标准写法是 2024-08-17T02:24:50Z, 可以带小数秒; 宽松模式也接受时区偏移、省略 Z 和只有日期的写法
+    fn convert_date(text: &str, lenient: bool) -> Result<Date, ErrorKind> {
+        let s = text.trim();
+        if let Ok(date) = s.parse() {
+            return Ok(date);
+        }
+        if !lenient {
+            return Err(ErrorKind::BadDate(text.to_string()));
+        }
+        if let Ok(date) = DateTime::parse_from_rfc3339(s) {
+            return Ok(date.to_utc().into());
+        }
+        if let Ok(date) = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f") {
+            return Ok(date.and_utc().into());
+        }
+        NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .map(|date| date.and_utc().into())
+            .ok_or_else(|| ErrorKind::BadDate(text.to_string()))
+    }
+    fn convert_integer(text: &str) -> Result<Integer, ErrorKind> {
+        text.trim()
+            .parse()
+            .map_err(|_| ErrorKind::BadInteger(text.to_string()))
+    }
+    //读取文本内容并转换, 转换失败时定位到内容起始位置
+    fn parse_scalar<T>(
+        &mut self,
+        name: &str,
+        empty: bool,
+        convert: impl Fn(&str) -> Result<T, ErrorKind>,
+    ) -> Result<T, Error> {
+        let (start, content) = self.parse_content(name, empty)?;
+        convert(&content).map_err(|kind| self.error(kind, start))
+    }
+    fn parse_dict(&mut self, empty: bool) -> Result<IndexMap<String, Plist>, Error> {
         let mut dict = IndexMap::new();
-        for (key, value) in values {
-            dict.insert(key.to_string(), value);
-        }
-        let (input, _) = multispace0(input)?;
-        let (input, _) = tag("</dict>")(input)?;
-        Ok((input, dict))
-    }
-    fn parse_value(input: &str) -> IResult<&str, Plist> {
-        let (input, _) = multispace0(input)?;
-        if input.starts_with("<string>") || input.starts_with("<string/>") {
-            map(Self::parse_string, Plist::String).parse(input)
-        } else if input.starts_with("<real>") {
-            map(Self::parse_float, Plist::Float).parse(input)
-        } else if input.starts_with("<date>") {
-            map(Self::parse_date, Plist::Date).parse(input)
-        } else if input.starts_with("<data>") || input.starts_with("<data/>") {
-            map(Self::parse_data, Plist::Data).parse(input)
-        } else if input.starts_with("<integer>") {
-            map(Self::parse_integer, Plist::Integer).parse(input)
-        } else if input.starts_with("<true") || input.starts_with("<false") {
-            map(Self::parse_boolean, Plist::Boolean).parse(input)
-        } else if input.starts_with("<dict>") || input.starts_with("<dict/>") {
-            map(Self::parse_dict, Plist::Dictionary).parse(input)
+        if empty {
+            return Ok(dict);
+        }
+        loop {
+            match self.next_markup()? {
+                (_, Token::End("dict")) => return Ok(dict),
+                (offset, Token::Start { name: "key", empty }) => {
+                    let (_, key) = self.parse_content("key", empty)?;
+                    let value = self.parse_value()?;
+                    match (dict.contains_key(&key), self.options.duplicate_keys) {
+                        (true, DuplicateKeys::Error) => {
+                            return Err(self.error(ErrorKind::DuplicateKey(key), offset));
+                        }
+                        (true, DuplicateKeys::KeepFirst) => {}
+                        _ => {
+                            dict.insert(key, value);
+                        }
+                    }
+                }
+                (offset, _) => return Err(self.error(ErrorKind::InvalidSyntax, offset)),
+            }
+        }
+    }
+    fn parse_array(&mut self, empty: bool) -> Result<Vec<Plist>, Error> {
+        let mut array = vec![];
+        if empty {
+            return Ok(array);
+        }
+        loop {
+            match self.next_markup()? {
+                (_, Token::End("array")) => return Ok(array),
+                (offset, Token::Start { name, empty }) => {
+                    array.push(self.parse_element(offset, name, empty)?)
+                }
+                (offset, _) => return Err(self.error(ErrorKind::InvalidSyntax, offset)),
+            }
+        }
+    }
+    //数组和字典需要检查嵌套深度
+    fn parse_container(&mut self, offset: usize, name: &str, empty: bool) -> Result<Plist, Error> {
+        if self.depth >= self.options.max_depth {
+            let kind = ErrorKind::DepthLimitExceeded(self.options.max_depth);
+            return Err(self.error(kind, offset));
+        }
+        self.depth += 1;
+        let result = if name == "dict" {
+            self.parse_dict(empty).map(Plist::Dictionary)
         } else {
-            map(Self::parse_array, Plist::Array).parse(input)
-        }
-    }
-    fn parse_array(input: &str) -> IResult<&str, Vec<Plist>> {
-        let (input, _) = multispace0(input)?;
-        if input.starts_with("<array/>") {
-            let (input, _) = tag("<array/>")(input)?;
-            return Ok((input, vec![]));
-        }
-        let (input, _) = (tag("<array>"), multispace0).parse(input)?;
-        let (input, values) = many0(Self::parse_value).parse(input)?;
-        let (input, _) = (multispace0, tag("</array>"), multispace0).parse(input)?;
-        Ok((input, values))
-    }
-    pub fn parse(input: &[u8]) -> Result<Plist, Error> {
-        let input = String::from_utf8_lossy(input).to_string();
-        let input = input.as_str();
-        let (input, _) = take_until("<plist")(input)?; //skip <?xml version="1.0" encoding="UTF-8"?>
-        let (input, _) = terminated(is_not(">"), tag(">")).parse(input)?; //skip <plist ..>
-        let (input, value) = map(Self::parse_dict, Plist::Dictionary).parse(input)?;
-        let (_, _) = (multispace0, tag("</plist>"), multispace0).parse(input)?;
+            self.parse_array(empty).map(Plist::Array)
+        };
+        self.depth -= 1;
+        result
+    }
+    fn parse_element(&mut self, offset: usize, name: &str, empty: bool) -> Result<Plist, Error> {
+        self.objects += 1;
+        if self.objects > self.max_objects {
+            let kind = ErrorKind::ObjectLimitExceeded(self.max_objects);
+            return Err(self.error(kind, offset));
+        }
+        match name {
+            "dict" | "array" => self.parse_container(offset, name, empty),
+            _ => self.parse_leaf(offset, name, empty),
+        }
+    }
+    //标量单独解析, 容器递归路径上的栈帧尽量小
+    fn parse_leaf(&mut self, offset: usize, name: &str, empty: bool) -> Result<Plist, Error> {
+        let lenient_dates = self.options.lenient_dates;
+        let keep_real_text = self.options.keep_real_text;
+        let value = match name {
+            "string" => Plist::String(self.parse_content(name, empty)?.1),
+            "integer" => Plist::Integer(self.parse_scalar(name, empty, Self::convert_integer)?),
+            "real" => Plist::Float(
+                self.parse_scalar(name, empty, |s| Self::convert_float(s, keep_real_text))?,
+            ),
+            "date" => Plist::Date(
+                self.parse_scalar(name, empty, |s| Self::convert_date(s, lenient_dates))?,
+            ),
+            "data" => Plist::Data(self.parse_scalar(name, empty, base64::decode)?),
+            "true" | "false" => {
+                let (start, content) = self.parse_content(name, empty)?;
+                if !content.trim().is_empty() {
+                    return Err(self.error(ErrorKind::InvalidSyntax, start));
+                }
+                Plist::Boolean(name == "true")
+            }
+            _ => return Err(self.error(ErrorKind::UnknownTag(name.to_string()), offset)),
+        };
         Ok(value)
     }
+    fn parse_value(&mut self) -> Result<Plist, Error> {
+        match self.next_markup()? {
+            (offset, Token::Start { name, empty }) => self.parse_element(offset, name, empty),
+            (offset, _) => Err(self.error(ErrorKind::InvalidSyntax, offset)),
+        }
+    }
+    pub fn recover_uids(value: Plist) -> Plist {
+        match value {
+            Plist::Array(list) => Plist::Array(list.into_iter().map(Self::recover_uids).collect()),
+            Plist::Dictionary(dict) => {
+                if dict.len() == 1
+                    && let Some(Plist::Integer(uid)) = dict.get("CF$UID")
+                    && let Some(uid) = uid.as_unsigned()
+                {
+                    return Plist::Uid(uid);
+                }
+                Plist::Dictionary(
+                    dict.into_iter()
+                        .map(|(key, value)| (key, Self::recover_uids(value)))
+                        .collect(),
+                )
+            }
+            value => value,
+        }
+    }
+    //<?xml ... encoding="..."?> 声明的编码, 没有声明时为 UTF-8; prefix 是文档之前已解码的内容, 用于计算错误位置
+    fn declared_encoding(prefix: &str, input: &[u8]) -> Result<Encoding, Error> {
+        let Some(end) = input
+            .strip_prefix(b"<?xml")
+            .and_then(|decl| decl.windows(2).position(|w| w == b"?>"))
+        else {
+            return Ok(Encoding::Utf8);
+        };
+        let decl = String::from_utf8_lossy(&input[..end + 5]);
+        let Some(value) = decl.find("encoding").and_then(|start| {
+            let rest = decl[start + 8..]
+                .trim_start()
+                .strip_prefix('=')?
+                .trim_start();
+            let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            let value = &rest[1..];
+            value.find(quote).map(|end| &value[..end])
+        }) else {
+            return Ok(Encoding::Utf8);
+        };
+        match value.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" | "us-ascii" | "ascii" => Ok(Encoding::Utf8),
+            //声明为 UTF-16 却是单字节内容, 通常是转码后没有修改声明, 按 UTF-8 处理
+            "utf-16" | "utf-16le" | "utf-16be" => Ok(Encoding::Utf8),
+            "iso-8859-1" | "iso_8859-1" | "latin1" | "latin-1" => Ok(Encoding::Latin1),
+            _ => {
+                let offset = value.as_ptr() as usize - decl.as_ptr() as usize;
+                let kind = ErrorKind::UnsupportedEncoding(value.to_string());
+                Err(Error::xml(
+                    kind,
+                    &format!("{}{}", prefix, decl),
+                    prefix.len() + offset,
+                ))
+            }
+        }
+    }
+    //<?xml 或 <plist 之前、</plist> 之后的字节不属于文档, 例如描述文件的签名数据
+    fn document(input: &[u8]) -> (&[u8], &[u8]) {
+        let find = |pattern: &[u8]| input.windows(pattern.len()).position(|w| w == pattern);
+        let Some(start) = [find(b"<?xml"), find(b"<plist")]
+            .into_iter()
+            .flatten()
+            .min()
+        else {
+            return (&[], input);
+        };
+        let end = input
+            .windows(8)
+            .rposition(|w| w == b"</plist>")
+            .map(|end| end + 8)
+            .filter(|end| *end > start)
+            .unwrap_or(input.len());
+        (&input[..start], &input[start..end])
+    }
+    //根据 BOM 和编码声明把输入解码成文本, 非法字符直接报错
+    //非严格模式下只解码文档部分, 之前的字节按 UTF-8 尽量解码后保留, 错误位置仍按完整的输入计算
+    fn decode(input: &[u8], strict: bool) -> Result<String, Error> {
+        let mut text = String::new();
+        let (encoding, data) = if let Some(data) = input.strip_prefix(b"\xEF\xBB\xBF") {
+            (Encoding::Utf8, data)
+        } else if let Some(data) = input.strip_prefix(b"\xFF\xFE") {
+            (Encoding::Utf16Le, data)
+        } else if let Some(data) = input.strip_prefix(b"\xFE\xFF") {
+            (Encoding::Utf16Be, data)
+        } else if input.starts_with(b"<\0?\0") {
+            (Encoding::Utf16Le, input)
+        } else if input.starts_with(b"\0<\0?") {
+            (Encoding::Utf16Be, input)
+        } else {
+            let (prefix, data) = if strict {
+                (&[][..], input)
+            } else {
+                Self::document(input)
+            };
+            text = String::from_utf8_lossy(prefix).into_owned();
+            (Self::declared_encoding(&text, data)?, data)
+        };
+        match encoding {
+            Encoding::Utf8 => match std::str::from_utf8(data) {
+                Ok(data) => {
+                    text.push_str(data);
+                    Ok(text)
+                }
+                Err(e) => {
+                    text.push_str(&String::from_utf8_lossy(&data[..e.valid_up_to()]));
+                    Err(Error::xml(ErrorKind::InvalidUtf8, &text, text.len()))
+                }
+            },
+            Encoding::Latin1 => {
+                text.extend(data.iter().map(|b| *b as char));
+                Ok(text)
+            }
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let chunks = data.chunks_exact(2);
+                let remainder = chunks.remainder();
+                let units = chunks.map(|c| match encoding {
+                    Encoding::Utf16Le => u16::from_le_bytes([c[0], c[1]]),
+                    _ => u16::from_be_bytes([c[0], c[1]]),
+                });
+                text.reserve(data.len() / 2);
+                for c in char::decode_utf16(units) {
+                    match c {
+                        Ok(c) => text.push(c),
+                        Err(_) => {
+                            return Err(Error::xml(ErrorKind::InvalidUtf16, &text, text.len()));
+                        }
+                    }
+                }
+                if !remainder.is_empty() {
+                    return Err(Error::xml(ErrorKind::InvalidUtf16, &text, text.len()));
+                }
+                Ok(text)
+            }
+        }
+    }
+    pub(crate) fn parse_with(input: &[u8], options: &ParseOptions) -> Result<Plist, Error> {
+        let text = Self::decode(input, options.strict)?;
+        //跳过 <plist 之前的内容, 严格模式下只允许 <?xml ...?>、DOCTYPE 和注释
+        let start = if options.strict {
+            0
+        } else {
+            text.find("<plist")
+                .ok_or_else(|| Error::xml(ErrorKind::UnexpectedEof, &text, text.len()))?
+        };
+        let mut reader = XmlReader {
+            text: &text,
+            input: &text[start..],
+            options: options.clone(),
+            max_objects: options.object_limit(input),
+            depth: 0,
+            objects: 0,
+        };
+        match reader.next_markup()? {
+            (
+                _,
+                Token::Start {
+                    name: "plist",
+                    empty: false,
+                },
+            ) => {}
+            (offset, _) => return Err(reader.error(ErrorKind::InvalidSyntax, offset)),
+        }
+        //根对象可以是任意类型
+        let value = reader.parse_value()?;
+        match reader.next_markup()? {
+            (_, Token::End("plist")) => {}
+            (offset, _) => return Err(reader.error(ErrorKind::InvalidSyntax, offset)),
+        }
+        //</plist> 之后的内容只有严格模式才检查
+        while options.strict
+            && let Some((offset, token)) = reader.next_token()?
+        {
+            if !matches!(token, Token::Text(text) if text.trim().is_empty()) {
+                return Err(reader.error(ErrorKind::InvalidSyntax, offset));
+            }
+        }
+        if options.recover_uids {
+            Ok(Self::recover_uids(value))
+        } else {
+            Ok(value)
+        }
+    }
 }
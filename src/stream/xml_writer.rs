@@ -1,55 +1,251 @@
+use crate::error::Error;
+use crate::options::XmlWriteOptions;
 use crate::plist::Plist;
+use crate::stream::base64;
+use crate::stream::text::TextBuffer;
+use indexmap::IndexMap;
+use std::fmt::{self, Write as _};
+use std::io::Write;
 
-pub trait XmlWriter {
-    fn convert_xml(&self, indent: usize) -> String;
+const DECLARATION: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+const DOCTYPE: &str = r#"<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">"#;
+
+//去掉小数末尾的 0 和多余的小数点
+fn trim(text: &str) -> &str {
+    match text.contains('.') {
+        true => text.trim_end_matches('0').trim_end_matches('.'),
+        false => text,
+    }
 }
-impl XmlWriter for Plist {
-    fn convert_xml(&self, indent: usize) -> String {
-        let indent_str = "\t".repeat(indent);
-        let mut xml = String::new();
-        match self {
-            Plist::Float(value) => xml.push_str(&format!("{}<real>{}</real>\n", indent_str, value)),
-            Plist::Array(list) => {
-                xml.push_str(&format!("{}<array>\n", indent_str));
-                for item in list {
-                    xml.push_str(&item.convert_xml(indent + 1));
-                }
-                xml.push_str(&format!("{}</array>\n", indent_str));
-            }
-            Plist::Dictionary(dict) => {
-                if dict.is_empty(){
-                    xml.push_str(&format!("{}<dict/>\n", indent_str));
-                }else{
-                    xml.push_str(&format!("{}<dict>\n", indent_str));
-                    for (key, value) in dict {
-                        xml.push_str(&format!("\t{}<key>{}</key>\n", indent_str, key));
-                        xml.push_str(&value.convert_xml(indent + 1)); // 递归增加缩进
-                    }
-                    xml.push_str(&format!("{}</dict>\n", indent_str));
-                }
+//与 CoreFoundation 相同的实数格式: %.17g, 0 写成 0.0; 直接写入输出, 不生成中间字符串
+struct RealText(f64);
+impl fmt::Display for RealText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.0;
+        if value.is_nan() {
+            return f.write_str("nan");
+        }
+        if value.is_infinite() {
+            return f.write_str(if value > 0.0 {
+                "+infinity"
+            } else {
+                "-infinity"
+            });
+        }
+        if value == 0.0 {
+            return f.write_str("0.0");
+        }
+        //先四舍五入到 17 位有效数字, 再按指数选择定点或科学计数法
+        let mut scientific = TextBuffer::default();
+        write!(scientific, "{:.16e}", value)?;
+        let (mantissa, exponent) = scientific
+            .as_str()
+            .split_once('e')
+            .unwrap_or((scientific.as_str(), "0"));
+        let exponent: i32 = exponent.parse().unwrap_or(0);
+        if (-4..17).contains(&exponent) {
+            let mut fixed = TextBuffer::default();
+            write!(fixed, "{:.*}", (16 - exponent) as usize, value)?;
+            f.write_str(trim(fixed.as_str()))
+        } else {
+            let sign = if exponent < 0 { '-' } else { '+' };
+            write!(f, "{}e{}{:02}", trim(mantissa), sign, exponent.abs())
+        }
+    }
+}
+
+//直接写入输出流, 每个节点不再生成中间字符串
+pub(crate) struct XmlWriter<'a, W: Write> {
+    output: W,
+    options: &'a XmlWriteOptions,
+    order: Vec<usize>, // 排序后的 key 下标, 各层字典共用, 写完一层后截断
+}
+impl<'a, W: Write> XmlWriter<'a, W> {
+    pub fn new(output: W, options: &'a XmlWriteOptions) -> Self {
+        XmlWriter {
+            output,
+            options,
+            order: vec![],
+        }
+    }
+    pub fn write(mut self, value: &Plist) -> Result<(), Error> {
+        if self.options.declaration {
+            self.output.write_all(DECLARATION.as_bytes())?;
+            self.end_line()?;
+        }
+        if self.options.doctype {
+            self.output.write_all(DOCTYPE.as_bytes())?;
+            self.end_line()?;
+        }
+        self.output.write_all(br#"<plist version="1.0">"#)?;
+        self.end_line()?;
+        self.write_value(value, 0)?;
+        self.output.write_all(b"</plist>")?;
+        if self.options.trailing_newline {
+            self.output.write_all(b"\n")?;
+        }
+        self.output.flush()?;
+        Ok(())
+    }
+    //紧凑模式下不缩进也不换行
+    fn start_line(&mut self, indent: usize) -> Result<(), Error> {
+        if !self.options.compact {
+            for _ in 0..indent {
+                self.output.write_all(self.options.indent.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+    fn end_line(&mut self) -> Result<(), Error> {
+        if !self.options.compact {
+            self.output.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+    //转义文本中的 & < > 字符, 保证输出合法的 XML
+    //\r 会被 XML 解析器规范化成 \n, 需要写成字符引用才能保留; apple_style 不转义 \r
+    fn write_escaped(&mut self, text: &str) -> Result<(), Error> {
+        let special: &[char] = if self.options.apple_style {
+            &['&', '<', '>']
+        } else {
+            &['&', '<', '>', '\r']
+        };
+        let mut rest = text;
+        while let Some(index) = rest.find(special) {
+            self.output.write_all(&rest.as_bytes()[..index])?;
+            let entity: &[u8] = match rest.as_bytes()[index] {
+                b'&' => b"&amp;",
+                b'<' => b"&lt;",
+                b'>' => b"&gt;",
+                _ => b"&#13;",
+            };
+            self.output.write_all(entity)?;
+            rest = &rest[index + 1..];
+        }
+        self.output.write_all(rest.as_bytes())?;
+        Ok(())
+    }
+    //开始标签独占一行
+    fn open(&mut self, tag: &[u8]) -> Result<(), Error> {
+        self.output.write_all(tag)?;
+        self.end_line()
+    }
+    fn close(&mut self, tag: &[u8], indent: usize) -> Result<(), Error> {
+        self.start_line(indent)?;
+        self.output.write_all(tag)?;
+        Ok(())
+    }
+    fn write_key(&mut self, key: &str, indent: usize) -> Result<(), Error> {
+        self.start_line(indent)?;
+        self.output.write_all(b"<key>")?;
+        self.write_escaped(key)?;
+        self.output.write_all(b"</key>")?;
+        self.end_line()
+    }
+    //write_value、write_array、write_dict 互相递归, 每层占用的栈空间要尽量小; 标量交给 write_scalar
+    fn write_value(&mut self, value: &Plist, indent: usize) -> Result<(), Error> {
+        self.start_line(indent)?;
+        match value {
+            //集合没有对应的 XML 类型, 按数组写出
+            Plist::Array(list) | Plist::Set(list) => self.write_array(list, indent)?,
+            Plist::Dictionary(dict) => self.write_dict(dict, indent)?,
+            _ => self.write_scalar(value, indent)?,
+        }
+        self.end_line()
+    }
+    fn write_array(&mut self, list: &[Plist], indent: usize) -> Result<(), Error> {
+        if list.is_empty() && self.options.apple_style {
+            return Ok(self.output.write_all(b"<array/>")?);
+        }
+        self.open(b"<array>")?;
+        for item in list {
+            self.write_value(item, indent + 1)?;
+        }
+        self.close(b"</array>", indent)
+    }
+    fn write_dict(&mut self, dict: &IndexMap<String, Plist>, indent: usize) -> Result<(), Error> {
+        if dict.is_empty() {
+            return Ok(self.output.write_all(b"<dict/>")?);
+        }
+        self.open(b"<dict>")?;
+        if self.options.apple_style {
+            self.write_sorted(dict, indent + 1)?;
+        } else {
+            for (key, value) in dict {
+                self.write_key(key, indent + 1)?;
+                self.write_value(value, indent + 1)?;
+            }
+        }
+        self.close(b"</dict>", indent)
+    }
+    //apple_style 按 UTF-16 编码单元排序 key
+    fn write_sorted(&mut self, dict: &IndexMap<String, Plist>, indent: usize) -> Result<(), Error> {
+        let start = self.order.len();
+        self.order.extend(0..dict.len());
+        let key = |index: usize| dict.get_index(index).map_or("", |(key, _)| key.as_str());
+        self.order[start..].sort_by(|a, b| key(*a).encode_utf16().cmp(key(*b).encode_utf16()));
+        for position in start..start + dict.len() {
+            if let Some((key, value)) = dict.get_index(self.order[position]) {
+                self.write_key(key, indent)?;
+                self.write_value(value, indent)?;
             }
+        }
+        self.order.truncate(start);
+        Ok(())
+    }
+    fn write_scalar(&mut self, value: &Plist, indent: usize) -> Result<(), Error> {
+        match value {
+            Plist::Float(value) if self.options.apple_style => {
+                write!(self.output, "<real>{}</real>", RealText(value.value()))?
+            }
+            Plist::Float(value) => write!(self.output, "<real>{}</real>", value)?,
             Plist::Boolean(value) => {
                 if *value {
-                    xml.push_str(&format!("{}<true/>\n", indent_str))
+                    self.output.write_all(b"<true/>")?
                 } else {
-                    xml.push_str(&format!("{}<false/>\n", indent_str))
+                    self.output.write_all(b"<false/>")?
                 }
             }
-            Plist::Integer(value) => {
-                xml.push_str(&format!("{}<integer>{}</integer>\n", indent_str, value))
-            }
+            Plist::Integer(value) => write!(self.output, "<integer>{}</integer>", value)?,
             Plist::String(value) => {
-                xml.push_str(&format!("{}<string>{}</string>\n", indent_str, value))
+                self.output.write_all(b"<string>")?;
+                self.write_escaped(value)?;
+                self.output.write_all(b"</string>")?;
+            }
+            Plist::Uid(value) => {
+                self.open(b"<dict>")?;
+                self.write_key("CF$UID", indent + 1)?;
+                self.start_line(indent + 1)?;
+                write!(self.output, "<integer>{}</integer>", value)?;
+                self.end_line()?;
+                self.close(b"</dict>", indent)?;
+            }
+            //apple_style 只写到整秒; 默认写出小数秒, 读回后与原来的值完全相同
+            Plist::Date(value) => match value.xml_text(!self.options.apple_style) {
+                Some(text) => write!(self.output, "<date>{}</date>", text)?,
+                None => return Err(Error::Error(format!("Date out of range: {}", value))),
+            },
+            //apple_style 每行最多 76 个字符, 行首的每级缩进按 8 个字符计算, 最多 8 级
+            Plist::Data(value) if self.options.apple_style => {
+                self.open(b"<data>")?;
+                let line_len = 76 - 8 * indent.min(8);
+                for line in value.chunks(line_len / 4 * 3) {
+                    self.start_line(indent.min(8))?;
+                    base64::encode_to(line, &mut self.output)?;
+                    self.end_line()?;
+                }
+                self.close(b"</data>", indent)?;
             }
-            Plist::Date(value) => xml.push_str(&format!("{}<date>{}</date>\n", indent_str, value)),
             Plist::Data(value) => {
-                let value = String::from_utf8_lossy(value).to_string();
-                xml.push_str(&format!(
-                    "{}<data>\n{}{}\n{}</data>\n",
-                    indent_str, indent_str, value, indent_str
-                ))
+                self.open(b"<data>")?;
+                self.start_line(indent)?;
+                base64::encode_to(value, &mut self.output)?;
+                self.end_line()?;
+                self.close(b"</data>", indent)?;
             }
+            //容器由 write_value 处理
+            Plist::Array(_) | Plist::Set(_) | Plist::Dictionary(_) => {}
         }
-        xml
+        Ok(())
     }
 }